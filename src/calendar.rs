@@ -0,0 +1,272 @@
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+use clap::ValueEnum;
+use rusqlite::{
+    ToSql,
+    types::{FromSql, FromSqlResult, ValueRef},
+};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt::Display;
+
+use crate::model::Holiday;
+
+/// Business-day adjustments are assumed to never move a due date by more than this.
+pub(crate) const MAX_ADJUSTMENT_DAYS: u64 = 31;
+
+/// Days on which bills cannot be paid, besides weekends.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Calendar {
+    holidays: HashSet<NaiveDate>,
+}
+
+impl Calendar {
+    pub(crate) fn is_business_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+}
+
+impl FromIterator<NaiveDate> for Calendar {
+    fn from_iter<I: IntoIterator<Item = NaiveDate>>(iter: I) -> Self {
+        Calendar {
+            holidays: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Where to move due dates falling on weekends or holidays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub(crate) enum BusinessDayRule {
+    /// Keep the due date as is.
+    #[default]
+    None,
+    /// Move to the next business day.
+    Following,
+    /// Move to the previous business day.
+    Preceding,
+    /// Move to the next business day, unless it is in the following month. Then, to the previous one.
+    ModifiedFollowing,
+}
+
+impl BusinessDayRule {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BusinessDayRule::None => "none",
+            BusinessDayRule::Following => "following",
+            BusinessDayRule::Preceding => "preceding",
+            BusinessDayRule::ModifiedFollowing => "modified-following",
+        }
+    }
+
+    /// The day a bill due on `date` can actually be paid on.
+    pub(crate) fn adjust(&self, date: NaiveDate, calendar: &Calendar) -> NaiveDate {
+        let step = |date: NaiveDate, forward: bool| {
+            let mut date = date;
+            while !calendar.is_business_day(date) {
+                date = if forward {
+                    date + Days::new(1)
+                } else {
+                    date - Days::new(1)
+                };
+            }
+            date
+        };
+
+        match self {
+            BusinessDayRule::None => date,
+            BusinessDayRule::Following => step(date, true),
+            BusinessDayRule::Preceding => step(date, false),
+            BusinessDayRule::ModifiedFollowing => {
+                let following = step(date, true);
+                if following.month() == date.month() {
+                    following
+                } else {
+                    step(date, false)
+                }
+            }
+        }
+    }
+}
+
+impl Display for BusinessDayRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromSql for BusinessDayRule {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("none") => FromSqlResult::Ok(BusinessDayRule::None),
+            Ok("following") => FromSqlResult::Ok(BusinessDayRule::Following),
+            Ok("preceding") => FromSqlResult::Ok(BusinessDayRule::Preceding),
+            Ok("modified-following") => FromSqlResult::Ok(BusinessDayRule::ModifiedFollowing),
+            _ => FromSqlResult::Err(rusqlite::types::FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for BusinessDayRule {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(ValueRef::Text(
+            self.as_str().as_bytes(),
+        )))
+    }
+}
+
+fn parse_ics_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+/// Reads the all-day events of an iCalendar file, such as the ones published for public holidays.
+/// Events spanning several days produce a holiday for each of them. Recurring events are not expanded.
+pub(crate) fn parse_ics(content: &str) -> color_eyre::Result<Vec<Holiday>> {
+    // Long lines are folded by starting the next ones with a space or tab.
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut holidays = Vec::new();
+    let mut event: Option<(Option<NaiveDate>, Option<NaiveDate>, String)> = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let property = name.split(';').next().unwrap_or_default();
+
+        match (property, event.as_mut()) {
+            ("BEGIN", None) if value == "VEVENT" => event = Some((None, None, String::new())),
+            ("DTSTART", Some((start, _, _))) => *start = parse_ics_date(value),
+            ("DTEND", Some((_, end, _))) => *end = parse_ics_date(value),
+            ("SUMMARY", Some((_, _, summary))) => *summary = value.replace("\\,", ","),
+            ("END", Some(_)) if value == "VEVENT" => {
+                let (start, end, summary) = event.take().expect("inside an event");
+                let Some(start) = start else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "event '{}' has no valid start date",
+                        summary
+                    )));
+                };
+                // The end date is exclusive.
+                let end = end
+                    .filter(|end| *end > start)
+                    .unwrap_or(start + Days::new(1));
+
+                holidays.extend(
+                    start
+                        .iter_days()
+                        .take_while(|date| *date < end)
+                        .map(|date| Holiday {
+                            date,
+                            name: summary.clone(),
+                        }),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    Ok(holidays)
+}
+
+#[derive(Deserialize)]
+struct CsvHoliday {
+    date: NaiveDate,
+    #[serde(default)]
+    name: String,
+}
+
+/// Reads a CSV file with a `date` column in %Y-%m-%d format and an optional `name` one.
+pub(crate) fn parse_csv<R: std::io::Read>(reader: R) -> color_eyre::Result<Vec<Holiday>> {
+    csv::Reader::from_reader(reader)
+        .deserialize::<CsvHoliday>()
+        .map(|record| {
+            let record = record.map_err(|e| {
+                color_eyre::Report::msg(format!(
+                    "invalid holiday: {e}. Expecting a 'date' column such as '2025-12-25' and an optional 'name' one"
+                ))
+            })?;
+
+            Ok(Holiday {
+                date: record.date,
+                name: record.name,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_adjust() {
+        // 2025-05-01 is a Thursday holiday, 2025-05-31 a Saturday.
+        let calendar: Calendar = [date("2025-05-01")].into_iter().collect();
+        let adjust = |rule: BusinessDayRule, s| rule.adjust(date(s), &calendar).to_string();
+
+        assert_eq!(adjust(BusinessDayRule::None, "2025-05-01"), "2025-05-01");
+        assert_eq!(
+            adjust(BusinessDayRule::Following, "2025-05-01"),
+            "2025-05-02"
+        );
+        assert_eq!(
+            adjust(BusinessDayRule::Preceding, "2025-05-01"),
+            "2025-04-30"
+        );
+        assert_eq!(
+            adjust(BusinessDayRule::Following, "2025-05-10"),
+            "2025-05-12"
+        );
+        assert_eq!(
+            adjust(BusinessDayRule::Following, "2025-05-31"),
+            "2025-06-02"
+        );
+        assert_eq!(
+            adjust(BusinessDayRule::ModifiedFollowing, "2025-05-31"),
+            "2025-05-30"
+        );
+        assert_eq!(
+            adjust(BusinessDayRule::ModifiedFollowing, "2025-05-10"),
+            "2025-05-12"
+        );
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   BEGIN:VEVENT\r\n\
+                   DTSTART;VALUE=DATE:20251225\r\n\
+                   DTEND;VALUE=DATE:20251226\r\n\
+                   SUMMARY:Christmas\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   DTSTART;VALUE=DATE:20260216\r\n\
+                   DTEND;VALUE=DATE:20260218\r\n\
+                   SUMMARY:Carn\r\n \
+                   ival\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+
+        let holidays: Vec<_> = parse_ics(ics)
+            .unwrap()
+            .into_iter()
+            .map(|h| (h.date.to_string(), h.name))
+            .collect();
+        assert_eq!(
+            holidays,
+            [
+                ("2025-12-25".to_string(), "Christmas".to_string()),
+                ("2026-02-16".to_string(), "Carnival".to_string()),
+                ("2026-02-17".to_string(), "Carnival".to_string()),
+            ]
+        );
+    }
+}
@@ -1,20 +1,53 @@
-use crate::model::{NewExpense, NewPayment, Periodicity};
-use crate::queries::{add_expense, add_payment, delete_expense, get_entries, get_expense_by_name};
-use crate::utils::{generate_rows, get_next_due_date};
+use crate::calendar::{BusinessDayRule, parse_csv, parse_ics};
+use crate::import::{ImportRow, parse_mapping, read_csv};
+use crate::model::{
+    Amount, BudgetPeriod, BudgetTarget, Currency, Estimate, Expense, Holiday, Money, NewExpense,
+    NewPayment, Pause, Payment, Split, SplitRule,
+};
+use crate::output::{Columns, Format, print_rows};
+use crate::queries::{
+    add_account, add_expense, add_member, add_pause, add_payment, archive_expense, delete_account,
+    delete_budget, delete_expense, delete_holiday, delete_member, delete_pause, delete_payment,
+    delete_split, get_accounts, get_budgets, get_calendar, get_data_path, get_entries,
+    get_exchange_rate, get_exchange_rates, get_expense_by_name, get_expense_categories,
+    get_holidays, get_income_names, get_latest_payment, get_ledgers, get_members,
+    get_payment_by_id, get_payments, get_splits, set_budget, set_exchange_rate, set_holiday,
+    set_split, update_expense, update_pause, update_payment,
+};
+use crate::recurrence::{MonthEndPolicy, Recurrence, from_local};
+use crate::utils::{
+    ReportPeriod, RowDisplay, describe_expense, generate_account_rows, generate_balance_rows,
+    generate_budget_rows, generate_forecast_rows, generate_history_rows, generate_overdue_rows,
+    generate_report_rows, generate_rows, generate_settlement_rows, get_consecutive_due_dates,
+    get_due_date_on, get_due_date_to_pay, get_shares, is_paid,
+};
 
-use chrono::{Datelike, Local, NaiveTime};
-use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, Utc, Weekday};
+use clap::{Parser, Subcommand, ValueEnum};
 use color_eyre::eyre::Result;
 use rusqlite::{Connection, Error, ffi};
-use tabled::Table;
-use tabled::settings::object::{Row, Rows};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use tabled::settings::object::Rows;
 use tabled::settings::{Highlight, Modify};
+use tabled::{Table, Tabled};
 
 /// Expenses tracker
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 #[command(propagate_version = true)]
 pub(crate) struct Cli {
+    /// Path to the database file. Takes precedence over --ledger
+    #[arg(long, global = true, env = "EXPENSES_DB", value_name = "PATH")]
+    db: Option<PathBuf>,
+
+    /// Name of the ledger to use, so that unrelated expenses can be kept apart. Defaults to 'data'
+    #[arg(long, global = true, env = "EXPENSES_LEDGER")]
+    ledger: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -23,24 +56,202 @@ pub(crate) struct Cli {
 enum Commands {
     /// Lists all expenses
     List {
-        /// Adds color based on time left to pay
+        /// Adds color based on time left to pay. Expenses over budget, or in a category over budget, are shown in magenta
         #[arg(short, long)]
         color: bool,
+
+        /// Prints totals converted to this currency using the registered exchange rates. Only used by human-readable formats
+        #[arg(short, long)]
+        base: Option<Currency>,
+
+        /// Only show expenses of this category
+        #[arg(long)]
+        category: Option<String>,
+
+        /// Only show expenses with this tag. Can be given several times to require all of them
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Lists income instead of expenses
+        #[arg(long)]
+        income: bool,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
     },
     /// Adds an expense
     Add {
         /// Name of the expense. Will be used as an identifier
         name: String,
 
-        /// Periodicity of the expense
-        #[arg(short, long, value_enum, default_value = "monthly")]
-        period: Periodicity,
+        /// How often the expense is due. E.g., 'monthly', 'quarterly', 'every 2 weeks', 'mon,thu', '2nd tue', 'last fri' or an RRULE such as 'FREQ=MONTHLY;BYDAY=-1FR'
+        #[arg(short, long, default_value = "monthly")]
+        period: Recurrence,
 
         /// Date when to pay the expense. Will be used as reference for future payments. Should be in %Y-%m-%d format
         #[arg(short, long)]
         date: String,
+
+        /// What to do in months without the day of the reference date, such as the 31st in April
+        #[arg(long, value_enum, default_value = "clamp")]
+        month_end: MonthEndPolicy,
+
+        /// Where to move due dates falling on weekends or holidays
+        #[arg(long, value_enum, default_value = "none")]
+        business_day: BusinessDayRule,
+
+        /// Makes it a one-off expense, due only on DATE
+        #[arg(long, conflicts_with_all = ["installments", "until"])]
+        once: bool,
+
+        /// Registers money coming in, such as a salary or rent received, rather than a bill. It is then only shown by 'list --income', 'show' and 'balance', and registered as received with 'receive'
+        #[arg(long)]
+        income: bool,
+
+        /// Number of installments, for expenses paid off in a fixed number of times
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        installments: Option<u32>,
+
+        /// Last day the expense may be due on. Should be in %Y-%m-%d format
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Category of the expense. E.g., 'Utilities'
+        #[arg(long)]
+        category: Option<String>,
+
+        /// Free-form tag of the expense. Can be given several times
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Who the expense is paid to
+        #[arg(long)]
+        payee: Option<String>,
+
+        /// Account, contract or customer number the payee knows the expense by
+        #[arg(long)]
+        reference: Option<String>,
+
+        /// Where to pay the expense or check its bills
+        #[arg(long)]
+        url: Option<String>,
+
+        /// Anything else worth remembering about the expense
+        #[arg(long)]
+        notes: Option<String>,
+
+        /// Account the expense is usually paid from. E.g., 'Checking'
+        #[arg(long)]
+        account: Option<String>,
+
+        /// Expected amount to be paid on each due date. E.g., '129.90'
+        #[arg(short, long)]
+        amount: Option<Amount>,
+
+        /// ISO 4217 code of the currency the expense is billed in. E.g., 'BRL'
+        #[arg(short = 'C', long, env = "EXPENSES_CURRENCY")]
+        currency: Option<Currency>,
+
+        /// Marks the amount as varying between bills. The next one is then estimated from past payments, and --amount is only used until the first one
+        #[arg(long, value_name = "ESTIMATE", num_args = 0..=1, default_missing_value = "average")]
+        variable: Option<Estimate>,
+    },
+    /// Edits an existing expense, keeping its payments
+    Edit {
+        /// Name of the expense to edit
+        name: String,
+
+        /// New name of the expense
+        #[arg(long)]
+        rename: Option<String>,
+
+        /// New recurrence of the expense. Accepts the same values as 'add'
+        #[arg(short, long)]
+        period: Option<Recurrence>,
+
+        /// New reference date for the due dates. Should be in %Y-%m-%d format
+        #[arg(short, long)]
+        date: Option<String>,
+
+        /// New policy for months without the day of the reference date
+        #[arg(long, value_enum)]
+        month_end: Option<MonthEndPolicy>,
+
+        /// New rule for due dates falling on weekends or holidays
+        #[arg(long, value_enum)]
+        business_day: Option<BusinessDayRule>,
+
+        /// New number of installments
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "no_installments")]
+        installments: Option<u32>,
+
+        /// Removes the number of installments, so that the expense is due until further notice
+        #[arg(long)]
+        no_installments: bool,
+
+        /// New last day the expense may be due on. Should be in %Y-%m-%d format
+        #[arg(long, conflicts_with = "no_until")]
+        until: Option<String>,
+
+        /// Removes the last day the expense may be due on
+        #[arg(long)]
+        no_until: bool,
+
+        /// New category of the expense
+        #[arg(long, conflicts_with = "no_category")]
+        category: Option<String>,
+
+        /// Removes the expense from its category
+        #[arg(long)]
+        no_category: bool,
+
+        /// Tag to add to the expense. Can be given several times
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Tag to remove from the expense. Can be given several times
+        #[arg(long = "untag", value_name = "TAG")]
+        untags: Vec<String>,
+
+        /// New payee of the expense. An empty value removes it
+        #[arg(long)]
+        payee: Option<String>,
+
+        /// New account, contract or customer number. An empty value removes it
+        #[arg(long)]
+        reference: Option<String>,
+
+        /// New URL of the expense. An empty value removes it
+        #[arg(long)]
+        url: Option<String>,
+
+        /// New notes of the expense. An empty value removes them
+        #[arg(long)]
+        notes: Option<String>,
+
+        /// New account the expense is usually paid from. An empty value removes it
+        #[arg(long)]
+        account: Option<String>,
+
+        /// New expected amount to be paid on each due date
+        #[arg(short, long)]
+        amount: Option<Amount>,
+
+        /// New currency of the expense
+        #[arg(short = 'C', long)]
+        currency: Option<Currency>,
+
+        /// Marks the amount as varying between bills, or changes how it is estimated
+        #[arg(long, value_name = "ESTIMATE", num_args = 0..=1, default_missing_value = "average")]
+        variable: Option<Estimate>,
+
+        /// Marks the amount as the same on every bill
+        #[arg(long, conflicts_with = "variable")]
+        fixed: bool,
     },
-    /// Registers a payment to an expense
+    /// Registers a payment to an expense, or that an income was received
+    #[command(alias = "receive")]
     Pay {
         /// Name of the expense to pay
         name: String,
@@ -48,110 +259,2363 @@ enum Commands {
         /// When the expense was paid. If not specified, current time is assumed
         #[arg(short, long)]
         date: Option<String>,
+
+        /// Amount actually paid for each period. If not specified, the expected amount of the expense is assumed
+        #[arg(short, long)]
+        amount: Option<Amount>,
+
+        /// Currency of the amount paid. If not specified, the currency of the expense is assumed
+        #[arg(short = 'C', long)]
+        currency: Option<Currency>,
+
+        /// Due date being paid. If not specified, the next due date is assumed. Should be in %Y-%m-%d format
+        #[arg(long = "for", value_name = "DUE_DATE")]
+        for_date: Option<String>,
+
+        /// Amount of consecutive periods paid at once, starting at the chosen due date
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        periods: u32,
+
+        /// How the expense was paid. E.g., 'card' or 'bank transfer'
+        #[arg(long)]
+        method: Option<String>,
+
+        /// Confirmation or transaction code of the payment
+        #[arg(long)]
+        confirmation: Option<String>,
+
+        /// Anything else worth remembering about the payment
+        #[arg(long)]
+        note: Option<String>,
+
+        /// Household member who made the payment
+        #[arg(long, value_name = "MEMBER")]
+        by: Option<String>,
+
+        /// Account the payment was made from. If not specified, the account of the expense is assumed
+        #[arg(long)]
+        account: Option<String>,
     },
-    /// Deletes an expense
+    /// Shows everything known about an expense, along with its latest payments
+    Show {
+        /// Name of the expense
+        name: String,
+
+        /// Number of payments to show
+        #[arg(short = 'n', long, default_value_t = 5)]
+        payments: usize,
+    },
+    /// Removes the payment covering the latest paid due date of an expense
+    Unpay {
+        /// Name of the expense
+        name: String,
+    },
+    /// Manages individual payments, identified by the ids shown in 'history'
+    Payment {
+        #[command(subcommand)]
+        command: PaymentCommands,
+    },
+    /// Deletes an expense along with its payments. Prefer 'archive' to retire an expense
     Delete {
         /// Name of the expense to delete
         name: String,
+
+        /// Deletes the expense even if it has payments
+        #[arg(long)]
+        force: bool,
+    },
+    /// Stops an expense from being due for a while, or until resumed
+    Pause {
+        /// Name of the expense to pause
+        name: String,
+
+        /// First day of the pause. If not specified, today is assumed. Should be in %Y-%m-%d format
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Last day of the pause. If not specified, the expense stays paused until resumed. Should be in %Y-%m-%d format
+        #[arg(long)]
+        until: Option<String>,
+    },
+    /// Ends the current or upcoming pause of an expense
+    Resume {
+        /// Name of the expense to resume
+        name: String,
+    },
+    /// Hides an expense from 'list' and 'overdue', keeping its payments in 'history'
+    Archive {
+        /// Name of the expense to archive
+        name: String,
+    },
+    /// Brings back an archived expense
+    Unarchive {
+        /// Name of the expense to unarchive
+        name: String,
+    },
+    /// Lists every payment registered, with how early or late it was paid
+    History {
+        /// Only show payments of this expense
+        name: Option<String>,
+
+        /// Only show payments made on or after this date. Should be in %Y-%m-%d format
+        #[arg(short, long)]
+        since: Option<String>,
+
+        /// Only show payments made on or before this date. Should be in %Y-%m-%d format
+        #[arg(short, long)]
+        until: Option<String>,
+
+        /// Column to sort by
+        #[arg(long, value_enum, default_value = "paid-at")]
+        sort: HistorySort,
+
+        /// Reverses the sorting order
+        #[arg(short, long)]
+        reverse: bool,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Lists every past due date that has not been paid
+    Overdue {
+        /// Only show overdue payments of this expense
+        name: Option<String>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Lists every unpaid due date from today until a given date, with weekly and monthly totals
+    Forecast {
+        /// Last day of the forecast. Should be in %Y-%m-%d format
+        #[arg(short, long)]
+        until: String,
+
+        /// Currency to convert the totals to, using the registered exchange rates
+        #[arg(short, long)]
+        base: Option<Currency>,
+
+        /// Output format. Totals are only shown for tables
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Sums up past payments per period, with their count, average and change from the previous period
+    Report {
+        /// Length of the periods
+        #[arg(long, value_enum, default_value = "month")]
+        by: ReportPeriod,
+
+        /// Only include payments of expenses in this category
+        #[arg(long)]
+        category: Option<String>,
+
+        /// Currency to convert the payments to, using the exchange rates of the days they were made
+        #[arg(short, long)]
+        base: Option<Currency>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Shows the income and expenses due in a month, what was paid or received so far and the projected net
+    Balance {
+        /// Month to show, in %Y-%m format. Defaults to the current one
+        #[arg(short, long)]
+        month: Option<String>,
+
+        /// Currency to convert the amounts to, using the exchange rates of the days they are due
+        #[arg(short, long)]
+        base: Option<Currency>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Lists the ledgers available in the data directory
+    Ledgers,
+    /// Manages exchange rates between currencies
+    Rate {
+        #[command(subcommand)]
+        command: RateCommands,
+    },
+    /// Manages the holidays taken into account by business-day rules
+    Holiday {
+        #[command(subcommand)]
+        command: HolidayCommands,
+    },
+    /// Imports expenses and payments in bulk
+    Import {
+        #[command(subcommand)]
+        command: ImportCommands,
+    },
+    /// Manages the household members expenses can be split between
+    Member {
+        #[command(subcommand)]
+        command: MemberCommands,
+    },
+    /// Splits the payments of an expense between household members
+    Split {
+        /// Name of the expense
+        name: String,
+
+        /// How the payments are shared
+        #[arg(short, long, value_enum, default_value = "equal")]
+        rule: SplitRule,
+
+        /// Members sharing the expense. Percentages and fixed amounts go after the name, e.g., 'alice=60'
+        #[arg(required_unless_present = "clear", conflicts_with = "clear")]
+        members: Vec<String>,
+
+        /// Stops splitting the expense
+        #[arg(long)]
+        clear: bool,
+    },
+    /// Shows who owes whom for the split expenses paid in a date range
+    Settle {
+        /// Only include payments made on or after this date. Should be in %Y-%m-%d format
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only include payments made on or before this date. Should be in %Y-%m-%d format
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Currency to convert the payments to, using the exchange rates of the days they were made
+        #[arg(short, long)]
+        base: Option<Currency>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Manages the accounts expenses are paid from
+    Account {
+        #[command(subcommand)]
+        command: AccountCommands,
+    },
+    /// Shows the unpaid due dates of the coming days per account, to know which ones need topping up
+    Accounts {
+        /// How many days ahead to look, from today
+        #[arg(short, long, default_value_t = 30)]
+        days: u32,
+
+        /// Currency to convert the amounts to, using the exchange rates of the days they are due
+        #[arg(short, long)]
+        base: Option<Currency>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Shows how much was spent against each budget this month or year, or manages budgets
+    Budget {
+        #[command(subcommand)]
+        command: Option<BudgetCommands>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "table")]
+        format: Format,
+    },
+}
+
+#[derive(Subcommand)]
+enum PaymentCommands {
+    /// Deletes a payment
+    Delete {
+        /// Id of the payment
+        id: i32,
+    },
+    /// Edits a payment
+    Edit {
+        /// Id of the payment
+        id: i32,
+
+        /// When the expense was paid. Should be in RFC3339 format
+        #[arg(long)]
+        paid_at: Option<String>,
+
+        /// Due date covered by the payment. Should be in %Y-%m-%d format
+        #[arg(long = "for", value_name = "DUE_DATE")]
+        for_date: Option<String>,
+
+        /// Amount paid
+        #[arg(short, long)]
+        amount: Option<Amount>,
+
+        /// Currency of the amount paid
+        #[arg(short = 'C', long)]
+        currency: Option<Currency>,
+
+        /// How the expense was paid. An empty value removes it
+        #[arg(long)]
+        method: Option<String>,
+
+        /// Confirmation or transaction code of the payment. An empty value removes it
+        #[arg(long)]
+        confirmation: Option<String>,
+
+        /// Note about the payment. An empty value removes it
+        #[arg(long)]
+        note: Option<String>,
+
+        /// Household member who made the payment. An empty value removes it
+        #[arg(long, value_name = "MEMBER")]
+        by: Option<String>,
+
+        /// Account the payment was made from. An empty value removes it
+        #[arg(long)]
+        account: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportCommands {
+    /// Imports a CSV file with a header row. Rows with a 'paid_at' are payments of the expense called 'name', the others new expenses. Columns are named after the options of 'add' and 'pay', in snake case, with tags separated by ';'
+    Csv {
+        /// Path to the file
+        file: PathBuf,
+
+        /// Maps a column of the file to a field, as 'COLUMN=FIELD'. Columns named after a field, such as 'name', 'period' or 'date', need none. Can be given several times
+        #[arg(short, long = "map", value_name = "COLUMN=FIELD", value_parser = parse_mapping)]
+        mappings: Vec<(String, &'static str)>,
+
+        /// Checks every row and shows what would be imported, without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemberCommands {
+    /// Adds a household member
+    Add {
+        /// Name of the member
+        name: String,
     },
+    /// Removes a member who has no payments and shares no expense
+    Delete {
+        /// Name of the member
+        name: String,
+    },
+    /// Lists all members
+    List,
 }
 
-impl Cli {
-    pub(crate) fn run(&self, conn: &Connection) -> Result<()> {
-        match &self.command {
-            Commands::List { color } => {
-                let entries = get_entries(conn).unwrap();
-                let rows = generate_rows(&entries);
-                let mut table = Table::new(&rows);
-
-                if *color {
-                    for (i, row) in rows.iter().enumerate() {
-                        let color = if row.is_paid() {
-                            tabled::settings::Color::FG_GREEN
-                        } else {
-                            row.periodicity.get_row_color_on_time_left(row.days_left)
-                        };
+#[derive(Subcommand)]
+enum AccountCommands {
+    /// Adds an account expenses are paid from, such as a checking account or a credit card
+    Add {
+        /// Name of the account
+        name: String,
+    },
+    /// Removes an account no expense or payment uses
+    Delete {
+        /// Name of the account
+        name: String,
+    },
+    /// Lists all accounts
+    List,
+}
 
-                        table.with(Modify::new(Rows::one(i + 1)).with(&color));
-                        table.with(Highlight::colored(Rows::one(i + 1), color));
-                    }
-                }
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum HistorySort {
+    PaidAt,
+    DueDate,
+    Name,
+    Amount,
+    /// How late the payment was, latest last
+    Delay,
+}
 
-                println!("{table}");
-            }
-            Commands::Add { name, period, date } => {
-                let naive_date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d");
-                let Ok(naive_date) = naive_date else {
-                    return Err(color_eyre::Report::msg(format!(
-                        "invalid date: {}. Expecting something like '1996-12-19'",
-                        date
-                    )));
-                };
-                if naive_date.day() > 28 && !matches!(period, Periodicity::Weekly) {
-                    return Err(color_eyre::Report::msg(
-                        "please choose a day smaller than 29 when using this period",
-                    ));
-                }
-                let naive_datetime = chrono::NaiveDateTime::new(
-                    naive_date,
-                    NaiveTime::from_hms_opt(0, 0, 1).expect("arguments are valid"),
-                );
+#[derive(Subcommand)]
+enum RateCommands {
+    /// Sets how much one unit of a currency is worth in another one
+    Set {
+        /// Currency being converted. E.g., 'EUR'
+        from: Currency,
 
-                let new_expense = NewExpense {
-                    created_at: chrono::Utc::now(),
-                    due_date_reference: naive_datetime.and_local_timezone(Local).unwrap().to_utc(),
-                    name,
-                    periodicity: *period,
-                };
-                add_expense(conn, &new_expense)?;
-            }
-            Commands::Pay { name, date } => {
-                let date = if let Some(date) = date {
-                    let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(date) else {
-                        return Err(color_eyre::Report::msg(format!(
-                            "invalid RFC3339 date: {}. Expecting something like '1996-12-19T16:39:57-08:00'",
-                            date
-                        )));
-                    };
+        /// Currency being converted to. E.g., 'BRL'
+        to: Currency,
 
-                    datetime.to_utc()
-                } else {
-                    chrono::Utc::now()
-                };
-                let expense = get_expense_by_name(conn, name)?;
-                let Some(expense) = expense else {
-                    return Err(color_eyre::Report::msg(format!(
-                        "expense with name {} does not exist",
-                        name
-                    )));
-                };
-                let next_due_date =
-                    get_next_due_date(&expense.due_date_reference, expense.periodicity);
-
-                let new_payment = NewPayment {
-                    created_at: chrono::Utc::now(),
-                    paid_at: date,
-                    expense_name: name,
-                    due_date_of_expense: next_due_date,
-                };
+        /// How much one unit of FROM is worth in TO. E.g., '6.1'
+        rate: Amount,
 
-                let add_payment_result = add_payment(conn, &new_payment);
+        /// Date from which the rate is valid. If not specified, today is assumed. Should be in %Y-%m-%d format
+        #[arg(short, long)]
+        date: Option<String>,
+    },
+    /// Lists all registered exchange rates
+    List,
+}
 
-                if let Err(Error::SqliteFailure(ffi::Error { extended_code, .. }, _)) =
-                    add_payment_result
-                    && extended_code == 787
-                {
-                    return Err(color_eyre::Report::msg(format!(
-                        "expense with name {} does not exist",
-                        name
-                    )));
-                }
+#[derive(Subcommand)]
+enum BudgetCommands {
+    /// Sets the spending limit of a category or expense, replacing the one for the same period
+    Set {
+        /// Most that should be spent per period. E.g., '500'
+        amount: Amount,
 
-                add_payment_result?;
-            }
-            Commands::Delete { name } => delete_expense(conn, name)?,
+        /// Category the limit applies to
+        #[arg(long, required_unless_present = "expense", conflicts_with = "expense")]
+        category: Option<String>,
+
+        /// Expense the limit applies to
+        #[arg(long)]
+        expense: Option<String>,
+
+        /// How long the limit lasts before starting over
+        #[arg(short, long, value_enum, default_value = "month")]
+        period: BudgetPeriod,
+
+        /// ISO 4217 code of the currency of the limit. E.g., 'BRL'
+        #[arg(short = 'C', long, env = "EXPENSES_CURRENCY")]
+        currency: Option<Currency>,
+    },
+    /// Removes the spending limit of a category or expense
+    Delete {
+        /// Category the limit applies to
+        #[arg(long, required_unless_present = "expense", conflicts_with = "expense")]
+        category: Option<String>,
+
+        /// Expense the limit applies to
+        #[arg(long)]
+        expense: Option<String>,
+
+        /// Period of the limit
+        #[arg(short, long, value_enum, default_value = "month")]
+        period: BudgetPeriod,
+    },
+}
+
+#[derive(Subcommand)]
+enum HolidayCommands {
+    /// Imports holidays from an iCalendar (.ics) or CSV file. CSV files need a 'date' column and may have a 'name' one
+    Import {
+        /// Path to the file
+        file: PathBuf,
+    },
+    /// Adds a single holiday
+    Add {
+        /// Date of the holiday. Should be in %Y-%m-%d format
+        date: String,
+
+        /// Name of the holiday
+        #[arg(default_value = "")]
+        name: String,
+    },
+    /// Removes a holiday
+    Delete {
+        /// Date of the holiday. Should be in %Y-%m-%d format
+        date: String,
+    },
+    /// Lists all registered holidays
+    List,
+}
+
+fn parse_datetime(date: &str) -> Result<DateTime<Utc>> {
+    chrono::DateTime::parse_from_rfc3339(date)
+        .map(|datetime| datetime.to_utc())
+        .map_err(|_| {
+            color_eyre::Report::msg(format!(
+                "invalid RFC3339 date: {}. Expecting something like '1996-12-19T16:39:57-08:00'",
+                date
+            ))
+        })
+}
+
+/// Due dates are anchored one second after the local midnight of the chosen day.
+fn to_due_date_reference(naive_date: NaiveDate) -> Result<DateTime<Utc>> {
+    let naive_datetime = chrono::NaiveDateTime::new(
+        naive_date,
+        NaiveTime::from_hms_opt(0, 0, 1).expect("arguments are valid"),
+    );
+
+    Ok(from_local(&Local, naive_datetime).to_utc())
+}
+
+/// Unpaid expenses due within this many days are counted as due soon in the sections of 'list'.
+const DUE_SOON_DAYS: i64 = 7;
+
+fn build_list_table(rows: &[&RowDisplay], color: bool) -> Table {
+    let mut table = Table::new(rows);
+
+    if color {
+        for (i, row) in rows.iter().enumerate() {
+            let color = if row.overdue_count > 0 {
+                tabled::settings::Color::FG_RED
+            } else if row.over_budget {
+                tabled::settings::Color::FG_MAGENTA
+            } else if row.is_paid {
+                tabled::settings::Color::FG_GREEN
+            } else if let Some(days_left) = row.days_left {
+                row.periodicity.get_row_color_on_time_left(days_left)
+            } else {
+                tabled::settings::Color::FG_BRIGHT_BLACK
+            };
+
+            table.with(Modify::new(Rows::one(i + 1)).with(&color));
+            table.with(Highlight::colored(Rows::one(i + 1), color));
         }
+    }
 
-        Ok(())
+    table
+}
+
+/// Converts `money` with the most recent exchange rate on or before `date`.
+fn convert(conn: &Connection, money: Money, base: Currency, date: NaiveDate) -> Result<Money> {
+    let Some(rate) = get_exchange_rate(conn, money.currency, base, date)? else {
+        return Err(color_eyre::Report::msg(format!(
+            "no exchange rate from {} to {} on or before {}. Register one with 'expenses rate set'",
+            money.currency, base, date
+        )));
+    };
+
+    Ok(Money {
+        amount: Amount(money.amount.0 * rate),
+        currency: base,
+    })
+}
+
+#[derive(Tabled, Serialize)]
+struct ImportRowDisplay {
+    line: u64,
+    kind: &'static str,
+    name: String,
+    /// Reference date of expenses, due date covered by payments.
+    date: NaiveDate,
+    amount: String,
+}
+
+impl Columns for ImportRowDisplay {
+    const COLUMNS: &'static [&'static str] = &["line", "kind", "name", "date", "amount"];
+}
+
+fn parse_field<T: std::str::FromStr<Err = String>>(
+    row: &ImportRow,
+    field: &str,
+) -> Result<Option<T>> {
+    row.get(field)
+        .map(|value| value.parse().map_err(color_eyre::Report::msg))
+        .transpose()
+}
+
+fn parse_value_field<T: ValueEnum>(row: &ImportRow, field: &str) -> Result<Option<T>> {
+    row.get(field)
+        .map(|value| {
+            T::from_str(value, true)
+                .map_err(|_| color_eyre::Report::msg(format!("invalid {}: {}", field, value)))
+        })
+        .transpose()
+}
+
+fn parse_bool_field(row: &ImportRow, field: &str) -> Result<bool> {
+    match row.get(field).map(str::to_lowercase).as_deref() {
+        None | Some("false" | "no" | "0") => Ok(false),
+        Some("true" | "yes" | "1") => Ok(true),
+        Some(value) => Err(color_eyre::Report::msg(format!(
+            "invalid {}: {}. Expecting 'true' or 'false'",
+            field, value
+        ))),
+    }
+}
+
+/// What 'add' is given for a new expense, from the command line or an imported row.
+struct ExpenseInput<'a> {
+    name: &'a str,
+    recurrence: Recurrence,
+    date: NaiveDate,
+    month_end: MonthEndPolicy,
+    business_day: BusinessDayRule,
+    once: bool,
+    income: bool,
+    installments: Option<u32>,
+    until: Option<NaiveDate>,
+    category: Option<&'a str>,
+    tags: Vec<&'a str>,
+    payee: Option<&'a str>,
+    reference: Option<&'a str>,
+    url: Option<&'a str>,
+    notes: Option<&'a str>,
+    account: Option<&'a str>,
+    amount: Option<Amount>,
+    currency: Option<Currency>,
+    estimate: Option<Estimate>,
+}
+
+/// Checks a new expense, the same way for 'add' and 'import csv'. The currency defaults to
+/// `EXPENSES_CURRENCY`.
+fn new_expense<'a>(conn: &Connection, input: ExpenseInput<'a>) -> Result<NewExpense<'a>> {
+    if get_expense_by_name(conn, input.name)?.is_some() {
+        return Err(color_eyre::Report::msg(format!(
+            "expense with name {} already exists",
+            input.name
+        )));
+    }
+    if input.installments == Some(0) {
+        return Err(color_eyre::Report::msg(
+            "invalid installments: 0. Expecting a positive number",
+        ));
+    }
+    if input.once && (input.installments.is_some() || input.until.is_some()) {
+        return Err(color_eyre::Report::msg(
+            "one-off expenses cannot have installments or an end date",
+        ));
+    }
+    let currency = match input.currency {
+        Some(currency) => Some(currency),
+        None => std::env::var("EXPENSES_CURRENCY")
+            .ok()
+            .map(|currency| currency.parse().map_err(color_eyre::Report::msg))
+            .transpose()?,
+    };
+    let account = input
+        .account
+        .and_then(non_empty)
+        .map(|account| find_registered("account", &get_accounts(conn)?, &account))
+        .transpose()?;
+
+    Ok(NewExpense {
+        created_at: chrono::Utc::now(),
+        name: input.name,
+        recurrence: input.recurrence,
+        due_date_reference: to_due_date_reference(input.date)?,
+        month_end: input.month_end,
+        business_day: input.business_day,
+        installments: if input.once {
+            Some(1)
+        } else {
+            input.installments
+        },
+        until: input.until,
+        amount: to_expected_amount(input.amount, currency)?,
+        estimate: input.estimate,
+        category: input.category.map(validate_label).transpose()?,
+        tags: input
+            .tags
+            .into_iter()
+            .map(validate_label)
+            .collect::<Result<_>>()?,
+        payee: input.payee.and_then(non_empty),
+        reference: input.reference.and_then(non_empty),
+        url: input.url.and_then(non_empty),
+        notes: input.notes.and_then(non_empty),
+        income: input.income,
+        account,
+    })
+}
+
+/// Adds the expense of an imported row, checked as by 'add'.
+fn import_expense(conn: &Connection, row: &ImportRow) -> Result<ImportRowDisplay> {
+    let Some(date) = row.get("date") else {
+        return Err(color_eyre::Report::msg(
+            "please specify the date of the expense, or a paid_at for a payment",
+        ));
+    };
+    let date = parse_date(date)?;
+    let installments = row
+        .get("installments")
+        .map(|installments| {
+            installments.parse().map_err(|_| {
+                color_eyre::Report::msg(format!(
+                    "invalid installments: {}. Expecting a positive number",
+                    installments
+                ))
+            })
+        })
+        .transpose()?;
+
+    let new_expense = new_expense(
+        conn,
+        ExpenseInput {
+            name: row.get("name").expect("rows are only imported with a name"),
+            recurrence: row
+                .get("period")
+                .unwrap_or("monthly")
+                .parse()
+                .map_err(color_eyre::Report::msg)?,
+            date,
+            month_end: parse_value_field(row, "month_end")?.unwrap_or(MonthEndPolicy::Clamp),
+            business_day: parse_value_field(row, "business_day")?.unwrap_or(BusinessDayRule::None),
+            once: parse_bool_field(row, "once")?,
+            income: parse_bool_field(row, "income")?,
+            installments,
+            until: row.get("until").map(parse_date).transpose()?,
+            category: row.get("category"),
+            tags: row
+                .get("tags")
+                .map_or(Vec::new(), |tags| tags.split(';').collect()),
+            payee: row.get("payee"),
+            reference: row.get("reference"),
+            url: row.get("url"),
+            notes: row.get("notes"),
+            account: row.get("account"),
+            amount: parse_field(row, "amount")?,
+            currency: parse_field(row, "currency")?,
+            estimate: parse_value_field(row, "variable")?,
+        },
+    )?;
+    add_expense(conn, &new_expense)?;
+
+    Ok(ImportRowDisplay {
+        line: row.line,
+        kind: if new_expense.income {
+            "income"
+        } else {
+            "expense"
+        },
+        name: new_expense.name.to_string(),
+        date,
+        amount: new_expense
+            .amount
+            .map_or("-".to_string(), |amount| amount.to_string()),
+    })
+}
+
+/// Registers the payment of an imported row, checked as by 'pay'. Payments cover the due date
+/// given as 'for', or else the one 'pay' would.
+fn import_payment(conn: &Connection, row: &ImportRow) -> Result<ImportRowDisplay> {
+    let name = row.get("name").expect("rows are only imported with a name");
+    let paid_at = row.get("paid_at").expect("payments have a paid_at");
+    let paid_at = match parse_date(paid_at) {
+        Ok(date) => to_due_date_reference(date)?,
+        Err(_) => parse_datetime(paid_at)?,
+    };
+    let expense = get_payable_expense(conn, name)?;
+    let for_date = row.get("for").map(parse_date).transpose()?;
+    let due_date = get_first_due_date(conn, &expense, for_date)?;
+    let due_date_naive = due_date.with_timezone(&Local).date_naive();
+    if is_paid(&expense, &get_payments(conn, Some(name))?, &due_date) {
+        return Err(color_eyre::Report::msg(format!(
+            "due date {} of expense {} is already paid",
+            due_date_naive, name
+        )));
+    }
+    let amount = to_paid_amount(
+        &expense,
+        parse_field(row, "amount")?,
+        parse_field(row, "currency")?,
+    )?;
+    let paid_by = row
+        .get("by")
+        .map(|by| find_registered("member", &get_members(conn)?, by))
+        .transpose()?;
+    let account = match row.get("account") {
+        Some(account) => Some(find_registered("account", &get_accounts(conn)?, account)?),
+        None => expense.account.clone(),
+    };
+
+    add_payment(
+        conn,
+        &NewPayment {
+            created_at: chrono::Utc::now(),
+            paid_at,
+            expense_name: name,
+            due_date_of_expense: due_date,
+            amount,
+            method: row.get("method").and_then(non_empty),
+            confirmation: row.get("confirmation").and_then(non_empty),
+            note: row.get("note").and_then(non_empty),
+            paid_by,
+            account,
+        },
+    )?;
+
+    Ok(ImportRowDisplay {
+        line: row.line,
+        kind: "payment",
+        name: name.to_string(),
+        date: due_date_naive,
+        amount: amount.map_or("-".to_string(), |amount| amount.to_string()),
+    })
+}
+
+#[derive(Tabled, Serialize)]
+struct TotalRowDisplay {
+    period: String,
+    total: String,
+}
+
+impl Columns for TotalRowDisplay {
+    const COLUMNS: &'static [&'static str] = &["period", "total"];
+}
+
+/// One row per period and currency, in order.
+fn to_total_rows(totals: BTreeMap<(String, Currency), Decimal>) -> Vec<TotalRowDisplay> {
+    totals
+        .into_iter()
+        .map(|((period, currency), total)| TotalRowDisplay {
+            period,
+            total: Money {
+                amount: Amount(total),
+                currency,
+            }
+            .to_string(),
+        })
+        .collect()
+}
+
+fn to_budget_target(category: &Option<String>, expense: &Option<String>) -> Result<BudgetTarget> {
+    match (category, expense) {
+        (Some(category), _) => Ok(BudgetTarget::Category(validate_label(category)?)),
+        (None, Some(expense)) => Ok(BudgetTarget::Expense(expense.clone())),
+        (None, None) => Err(color_eyre::Report::msg(
+            "please specify the budget with --category or --expense",
+        )),
+    }
+}
+
+/// Amounts of expenses need a currency, given or taken from `EXPENSES_CURRENCY`.
+fn to_expected_amount(amount: Option<Amount>, currency: Option<Currency>) -> Result<Option<Money>> {
+    match (amount, currency) {
+        (Some(amount), Some(currency)) => Ok(Some(Money { amount, currency })),
+        (Some(_), None) => Err(color_eyre::Report::msg(
+            "please specify the currency of the amount with --currency or EXPENSES_CURRENCY",
+        )),
+        (None, _) => Ok(None),
+    }
+}
+
+/// The amount paid defaults to the expected one, unless paid in another currency or variable.
+fn to_paid_amount(
+    expense: &Expense,
+    amount: Option<Amount>,
+    currency: Option<Currency>,
+) -> Result<Option<Money>> {
+    let expected_currency = expense.amount.map(|m| m.currency);
+
+    match (amount, currency.or(expected_currency)) {
+        (Some(amount), Some(currency)) => Ok(Some(Money { amount, currency })),
+        (Some(_), None) => Err(color_eyre::Report::msg(
+            "expense has no currency, please specify the one paid with --currency",
+        )),
+        (None, Some(currency)) if Some(currency) != expected_currency => {
+            Err(color_eyre::Report::msg(format!(
+                "please specify the amount paid in {} with --amount",
+                currency
+            )))
+        }
+        (None, _) if expense.estimate.is_some() => Err(color_eyre::Report::msg(format!(
+            "expense {} has a variable amount, please specify the amount paid with --amount",
+            expense.name
+        ))),
+        (None, _) => Ok(expense.amount),
+    }
+}
+
+/// Payments can only be registered to existing expenses that are not archived.
+fn get_payable_expense(conn: &Connection, name: &str) -> Result<Expense> {
+    let Some(expense) = get_expense_by_name(conn, name)? else {
+        return Err(color_eyre::Report::msg(format!(
+            "expense with name {} does not exist",
+            name
+        )));
+    };
+    if expense.archived_at.is_some() {
+        return Err(color_eyre::Report::msg(format!(
+            "expense {} is archived. Bring it back with 'expenses unarchive {}'",
+            name, name
+        )));
+    }
+
+    Ok(expense)
+}
+
+/// The due date on `for_date`, or else the one paid when none is chosen.
+fn get_first_due_date(
+    conn: &Connection,
+    expense: &Expense,
+    for_date: Option<NaiveDate>,
+) -> Result<DateTime<Utc>> {
+    let calendar = get_calendar(conn)?;
+
+    match for_date {
+        Some(for_date) => get_due_date_on(expense, &calendar, for_date).ok_or_else(|| {
+            color_eyre::Report::msg(format!(
+                "{} is not a due date of expense {}",
+                for_date, expense.name
+            ))
+        }),
+        None => get_due_date_to_pay(
+            expense,
+            &get_payments(conn, Some(&expense.name))?,
+            &calendar,
+        )
+        .ok_or_else(|| {
+            color_eyre::Report::msg(format!(
+                "expense {} has no due dates left to pay",
+                expense.name
+            ))
+        }),
+    }
+}
+
+/// Free-form details are trimmed, and left out when empty.
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Category and tag names are trimmed and cannot be empty.
+fn validate_label(label: &str) -> Result<String> {
+    let label = label.trim();
+    if label.is_empty() {
+        return Err(color_eyre::Report::msg(
+            "category and tag names cannot be empty",
+        ));
+    }
+
+    Ok(label.to_string())
+}
+
+/// Members and accounts are matched regardless of case, and referred to by their registered name.
+fn find_registered(kind: &str, registered: &[String], name: &str) -> Result<String> {
+    registered
+        .iter()
+        .find(|registered| registered.eq_ignore_ascii_case(name.trim()))
+        .cloned()
+        .ok_or_else(|| {
+            color_eyre::Report::msg(format!(
+                "{} {} does not exist. Add it with 'expenses {} add {}'",
+                kind, name, kind, name
+            ))
+        })
+}
+
+/// Parses the members of a split, given as 'alice' or 'alice=60'.
+fn parse_split(conn: &Connection, rule: SplitRule, members: &[String]) -> Result<Split> {
+    let registered = get_members(conn)?;
+    let mut shares: Vec<(String, Option<Amount>)> = Vec::new();
+
+    for member in members {
+        let (name, share) = match member.split_once('=') {
+            Some((name, share)) => (
+                name,
+                Some(share.parse::<Amount>().map_err(color_eyre::Report::msg)?),
+            ),
+            None => (member.as_str(), None),
+        };
+        let name = find_registered("member", &registered, name)?;
+        if shares.iter().any(|(other, _)| *other == name) {
+            return Err(color_eyre::Report::msg(format!(
+                "member {} is listed more than once",
+                name
+            )));
+        }
+
+        match (rule, share) {
+            (SplitRule::Equal, Some(_)) => {
+                return Err(color_eyre::Report::msg(
+                    "equal splits take no shares, only member names",
+                ));
+            }
+            (SplitRule::Percentage | SplitRule::Fixed, None) => {
+                return Err(color_eyre::Report::msg(format!(
+                    "please specify the {} of {} as '{}=<value>'",
+                    if rule == SplitRule::Percentage {
+                        "percentage"
+                    } else {
+                        "amount"
+                    },
+                    name,
+                    name
+                )));
+            }
+            _ => {}
+        }
+
+        shares.push((name, share));
+    }
+
+    if rule == SplitRule::Percentage {
+        let total: Decimal = shares
+            .iter()
+            .filter_map(|(_, share)| share.map(|s| s.0))
+            .sum();
+        if total != Decimal::ONE_HUNDRED {
+            return Err(color_eyre::Report::msg(format!(
+                "percentages add up to {}, not 100",
+                total
+            )));
+        }
+    }
+
+    Ok(Split { rule, shares })
+}
+
+fn parse_month(month: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").map_err(|_| {
+        color_eyre::Report::msg(format!(
+            "invalid month: {}. Expecting something like '2026-03'",
+            month
+        ))
+    })
+}
+
+/// Payments of expenses, leaving out the ones received for income.
+fn get_expense_payments(conn: &Connection) -> Result<Vec<Payment>> {
+    let incomes = get_income_names(conn)?;
+
+    Ok(get_payments(conn, None)?
+        .into_iter()
+        .filter(|payment| !incomes.contains(&payment.expense_name))
+        .collect())
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        color_eyre::Report::msg(format!(
+            "invalid date: {}. Expecting something like '1996-12-19'",
+            date
+        ))
+    })
+}
+
+impl Cli {
+    pub(crate) fn data_path(&self) -> Result<PathBuf> {
+        get_data_path(self.db.as_deref(), self.ledger.as_deref())
+    }
+
+    pub(crate) fn run(&self, conn: &Connection) -> Result<()> {
+        match &self.command {
+            Commands::List {
+                color,
+                base,
+                category,
+                tags,
+                income,
+                format,
+            } => {
+                let mut entries = get_entries(conn).unwrap();
+                entries.retain(|(expense, _)| {
+                    if expense.income != *income {
+                        return false;
+                    }
+                    let in_category = category.as_deref().is_none_or(|category| {
+                        expense
+                            .category
+                            .as_deref()
+                            .is_some_and(|c| c.eq_ignore_ascii_case(category.trim()))
+                    });
+                    let has_tags = tags.iter().all(|tag| {
+                        expense
+                            .tags
+                            .iter()
+                            .any(|t| t.eq_ignore_ascii_case(tag.trim()))
+                    });
+
+                    in_category && has_tags
+                });
+                let payments = get_payments(conn, None)?;
+                let calendar = get_calendar(conn)?;
+                let mut rows = generate_rows(&entries, &payments, &calendar);
+                if *color && !*income {
+                    let budgets = get_budgets(conn)?;
+                    let budget_rows = generate_budget_rows(
+                        &budgets,
+                        &get_expense_payments(conn)?,
+                        &get_expense_categories(conn)?,
+                        Local::now().date_naive(),
+                        |money, currency, date| convert(conn, money, currency, date),
+                    )?;
+                    for row in &mut rows {
+                        row.over_budget = budget_rows.iter().any(|budget| {
+                            budget.over_budget
+                                && match budget.target {
+                                    BudgetTarget::Category(category) => row
+                                        .category
+                                        .is_some_and(|c| c.eq_ignore_ascii_case(category)),
+                                    BudgetTarget::Expense(expense) => row.expense_name == expense,
+                                }
+                        });
+                    }
+                }
+
+                if *format != Format::Table {
+                    print_rows(&rows, *format)?;
+                } else if rows.iter().all(|row| row.category.is_none()) {
+                    println!(
+                        "{}",
+                        build_list_table(&rows.iter().collect::<Vec<_>>(), *color)
+                    );
+                } else {
+                    // One section per category, uncategorized expenses last.
+                    let mut sections: Vec<Option<&str>> =
+                        rows.iter().map(|row| row.category).collect();
+                    sections.sort_by_key(|category| {
+                        (category.is_none(), category.map(str::to_lowercase))
+                    });
+                    sections.dedup_by_key(|category| category.map(str::to_lowercase));
+
+                    for (i, section) in sections.into_iter().enumerate() {
+                        let section_rows: Vec<_> = rows
+                            .iter()
+                            .filter(|row| {
+                                row.category.map(str::to_lowercase)
+                                    == section.map(str::to_lowercase)
+                            })
+                            .collect();
+                        let unpaid = section_rows.iter().filter(|row| !row.is_paid).count();
+                        let due_soon = section_rows
+                            .iter()
+                            .filter(|row| {
+                                !row.is_paid
+                                    && row.days_left.is_some_and(|days_left| {
+                                        (0..=DUE_SOON_DAYS).contains(&days_left)
+                                    })
+                            })
+                            .count();
+
+                        if i > 0 {
+                            println!();
+                        }
+                        println!(
+                            "{} ({} unpaid, {} due soon)",
+                            section.unwrap_or("Uncategorized"),
+                            unpaid,
+                            due_soon
+                        );
+                        println!("{}", build_list_table(&section_rows, *color));
+                    }
+                }
+
+                if let Some(base) = base
+                    && format.is_human_readable()
+                {
+                    let mut total = Decimal::ZERO;
+                    let mut unpaid = Decimal::ZERO;
+
+                    for row in &rows {
+                        let (Some(money), Some(due_date)) = (row.amount, row.next_due_date) else {
+                            continue;
+                        };
+
+                        let converted = convert(conn, money, *base, due_date)?.amount.0;
+                        total += converted;
+                        if !row.is_paid {
+                            unpaid += converted;
+                        }
+                    }
+
+                    println!(
+                        "Total: {}",
+                        Money {
+                            amount: Amount(total),
+                            currency: *base
+                        }
+                    );
+                    println!(
+                        "Unpaid: {}",
+                        Money {
+                            amount: Amount(unpaid),
+                            currency: *base
+                        }
+                    );
+                }
+            }
+            Commands::Add {
+                name,
+                period,
+                date,
+                month_end,
+                business_day,
+                once,
+                income,
+                installments,
+                until,
+                category,
+                tags,
+                payee,
+                reference,
+                url,
+                notes,
+                account,
+                amount,
+                currency,
+                variable,
+            } => {
+                let new_expense = new_expense(
+                    conn,
+                    ExpenseInput {
+                        name,
+                        recurrence: period.clone(),
+                        date: parse_date(date)?,
+                        month_end: *month_end,
+                        business_day: *business_day,
+                        once: *once,
+                        income: *income,
+                        installments: *installments,
+                        until: until.as_deref().map(parse_date).transpose()?,
+                        category: category.as_deref(),
+                        tags: tags.iter().map(String::as_str).collect(),
+                        payee: payee.as_deref(),
+                        reference: reference.as_deref(),
+                        url: url.as_deref(),
+                        notes: notes.as_deref(),
+                        account: account.as_deref(),
+                        amount: *amount,
+                        currency: *currency,
+                        estimate: *variable,
+                    },
+                )?;
+
+                let tx = conn.unchecked_transaction()?;
+                add_expense(&tx, &new_expense)?;
+                tx.commit()?;
+            }
+            Commands::Edit {
+                name,
+                rename,
+                period,
+                date,
+                month_end,
+                business_day,
+                installments,
+                no_installments,
+                until,
+                no_until,
+                category,
+                no_category,
+                tags,
+                untags,
+                payee,
+                reference,
+                url,
+                notes,
+                account,
+                amount,
+                currency,
+                variable,
+                fixed,
+            } => {
+                let Some(mut expense) = get_expense_by_name(conn, name)? else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                };
+
+                if let Some(rename) = rename {
+                    expense.name = rename.clone();
+                }
+                if let Some(period) = period {
+                    expense.recurrence = period.clone();
+                }
+                if let Some(date) = date {
+                    expense.due_date_reference = to_due_date_reference(parse_date(date)?)?;
+                }
+                if let Some(month_end) = month_end {
+                    expense.month_end = *month_end;
+                }
+                if let Some(business_day) = business_day {
+                    expense.business_day = *business_day;
+                }
+                if let Some(installments) = installments {
+                    expense.installments = Some(*installments);
+                } else if *no_installments {
+                    expense.installments = None;
+                }
+                if let Some(until) = until {
+                    expense.until = Some(parse_date(until)?);
+                } else if *no_until {
+                    expense.until = None;
+                }
+                if let Some(category) = category {
+                    expense.category = Some(validate_label(category)?);
+                } else if *no_category {
+                    expense.category = None;
+                }
+                for tag in tags {
+                    let tag = validate_label(tag)?;
+                    if !expense.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                        expense.tags.push(tag);
+                    }
+                }
+                expense.tags.retain(|t| {
+                    !untags
+                        .iter()
+                        .any(|untag| untag.trim().eq_ignore_ascii_case(t))
+                });
+                for (field, value) in [
+                    (&mut expense.payee, payee),
+                    (&mut expense.reference, reference),
+                    (&mut expense.url, url),
+                    (&mut expense.notes, notes),
+                ] {
+                    if let Some(value) = value {
+                        *field = non_empty(value);
+                    }
+                }
+                if let Some(account) = account {
+                    expense.account = non_empty(account)
+                        .map(|account| find_registered("account", &get_accounts(conn)?, &account))
+                        .transpose()?;
+                }
+                match (amount, currency.or(expense.amount.map(|m| m.currency))) {
+                    (Some(amount), Some(currency)) => {
+                        expense.amount = Some(Money {
+                            amount: *amount,
+                            currency,
+                        })
+                    }
+                    (Some(_), None) => {
+                        return Err(color_eyre::Report::msg(
+                            "please specify the currency of the amount with --currency",
+                        ));
+                    }
+                    (None, Some(currency)) => {
+                        if let Some(money) = expense.amount.as_mut() {
+                            money.currency = currency;
+                        } else {
+                            return Err(color_eyre::Report::msg(
+                                "expense has no amount, please specify it with --amount",
+                            ));
+                        }
+                    }
+                    (None, None) => {}
+                }
+                if let Some(variable) = variable {
+                    expense.estimate = Some(*variable);
+                }
+                if *fixed {
+                    expense.estimate = None;
+                }
+
+                let tx = conn.unchecked_transaction()?;
+                let update_result = update_expense(&tx, &expense);
+                if let Err(Error::SqliteFailure(ffi::Error { extended_code, .. }, _)) =
+                    update_result
+                    && extended_code == 2067
+                {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} already exists",
+                        expense.name
+                    )));
+                }
+                update_result?;
+                tx.commit()?;
+
+                let calendar = get_calendar(conn)?;
+                for payment in get_payments(conn, Some(&expense.name))? {
+                    let due_date = payment
+                        .due_date_of_expense
+                        .with_timezone(&Local)
+                        .date_naive();
+                    let matches = get_due_date_on(&expense, &calendar, due_date)
+                        == Some(payment.due_date_of_expense);
+
+                    if !matches {
+                        eprintln!(
+                            "warning: payment {} (due {}) no longer matches a due date of {}. Fix it with 'expenses payment edit {} --for <DUE_DATE>'",
+                            payment.id, due_date, expense.name, payment.id
+                        );
+                    }
+                }
+            }
+            Commands::Pay {
+                name,
+                date,
+                amount,
+                currency,
+                for_date,
+                periods,
+                method,
+                confirmation,
+                note,
+                by,
+                account,
+            } => {
+                let paid_by = match by {
+                    Some(by) => Some(find_registered("member", &get_members(conn)?, by)?),
+                    None => None,
+                };
+                let date = match date {
+                    Some(date) => parse_datetime(date)?,
+                    None => chrono::Utc::now(),
+                };
+                let expense = get_payable_expense(conn, name)?;
+                let for_date = for_date.as_deref().map(parse_date).transpose()?;
+                let first_due_date = get_first_due_date(conn, &expense, for_date)?;
+                let due_dates =
+                    get_consecutive_due_dates(&expense, &first_due_date, *periods as usize);
+                if due_dates.len() < *periods as usize {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense {} only has {} due dates left from {}",
+                        name,
+                        due_dates.len(),
+                        first_due_date.with_timezone(&Local).date_naive()
+                    )));
+                }
+
+                let account = match account.as_deref().and_then(non_empty) {
+                    Some(account) => {
+                        Some(find_registered("account", &get_accounts(conn)?, &account)?)
+                    }
+                    None => expense.account.clone(),
+                };
+
+                let existing_payments = get_payments(conn, Some(name))?;
+                if let Some(already_paid) = due_dates
+                    .iter()
+                    .find(|due_date| is_paid(&expense, &existing_payments, due_date))
+                {
+                    return Err(color_eyre::Report::msg(format!(
+                        "due date {} of expense {} is already paid",
+                        already_paid.with_timezone(&Local).date_naive(),
+                        name
+                    )));
+                }
+
+                let paid_amount = to_paid_amount(&expense, *amount, *currency)?;
+
+                let tx = conn.unchecked_transaction()?;
+                for due_date in due_dates {
+                    let new_payment = NewPayment {
+                        created_at: chrono::Utc::now(),
+                        paid_at: date,
+                        expense_name: name,
+                        due_date_of_expense: due_date,
+                        amount: paid_amount,
+                        method: method.as_deref().and_then(non_empty),
+                        confirmation: confirmation.as_deref().and_then(non_empty),
+                        note: note.as_deref().and_then(non_empty),
+                        paid_by: paid_by.clone(),
+                        account: account.clone(),
+                    };
+
+                    let add_payment_result = add_payment(&tx, &new_payment);
+
+                    if let Err(Error::SqliteFailure(ffi::Error { extended_code, .. }, _)) =
+                        add_payment_result
+                        && extended_code == 787
+                    {
+                        return Err(color_eyre::Report::msg(format!(
+                            "expense with name {} does not exist",
+                            name
+                        )));
+                    }
+
+                    add_payment_result?;
+                }
+                tx.commit()?;
+            }
+            Commands::Unpay { name } => {
+                if get_expense_by_name(conn, name)?.is_none() {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                }
+
+                let Some(latest) = get_latest_payment(conn, name)? else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} has no payments",
+                        name
+                    )));
+                };
+
+                delete_payment(conn, latest.id)?;
+            }
+            Commands::Payment { command } => match command {
+                PaymentCommands::Delete { id } => {
+                    if !delete_payment(conn, *id)? {
+                        return Err(color_eyre::Report::msg(format!(
+                            "payment with id {} does not exist",
+                            id
+                        )));
+                    }
+                }
+                PaymentCommands::Edit {
+                    id,
+                    paid_at,
+                    for_date,
+                    amount,
+                    currency,
+                    method,
+                    confirmation,
+                    note,
+                    by,
+                    account,
+                } => {
+                    let Some(mut payment) = get_payment_by_id(conn, *id)? else {
+                        return Err(color_eyre::Report::msg(format!(
+                            "payment with id {} does not exist",
+                            id
+                        )));
+                    };
+
+                    if let Some(paid_at) = paid_at {
+                        payment.paid_at = parse_datetime(paid_at)?;
+                    }
+
+                    if let Some(for_date) = for_date {
+                        let for_date = parse_date(for_date)?;
+                        let expense = get_expense_by_name(conn, &payment.expense_name)?
+                            .expect("payments always reference an existing expense");
+                        let Some(due_date) =
+                            get_due_date_on(&expense, &get_calendar(conn)?, for_date)
+                        else {
+                            return Err(color_eyre::Report::msg(format!(
+                                "{} is not a due date of expense {}",
+                                for_date, expense.name
+                            )));
+                        };
+
+                        payment.due_date_of_expense = due_date;
+                    }
+
+                    match (amount, currency.or(payment.amount.map(|m| m.currency))) {
+                        (Some(amount), Some(currency)) => {
+                            payment.amount = Some(Money {
+                                amount: *amount,
+                                currency,
+                            })
+                        }
+                        (Some(_), None) => {
+                            return Err(color_eyre::Report::msg(
+                                "payment has no currency, please specify it with --currency",
+                            ));
+                        }
+                        (None, Some(currency)) => {
+                            if let Some(money) = payment.amount.as_mut() {
+                                money.currency = currency;
+                            } else {
+                                return Err(color_eyre::Report::msg(
+                                    "payment has no amount, please specify it with --amount",
+                                ));
+                            }
+                        }
+                        (None, None) => {}
+                    }
+
+                    for (field, value) in [
+                        (&mut payment.method, method),
+                        (&mut payment.confirmation, confirmation),
+                        (&mut payment.note, note),
+                    ] {
+                        if let Some(value) = value {
+                            *field = non_empty(value);
+                        }
+                    }
+
+                    if let Some(account) = account {
+                        payment.account = non_empty(account)
+                            .map(|account| {
+                                find_registered("account", &get_accounts(conn)?, &account)
+                            })
+                            .transpose()?;
+                    }
+
+                    if let Some(by) = by {
+                        payment.paid_by = match non_empty(by) {
+                            Some(by) => Some(find_registered("member", &get_members(conn)?, &by)?),
+                            None => None,
+                        };
+                    }
+
+                    update_payment(conn, &payment)?;
+                }
+            },
+            Commands::Delete { name, force } => {
+                if get_expense_by_name(conn, name)?.is_none() {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                }
+                if !force && !get_payments(conn, Some(name))?.is_empty() {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense {} has payments that would be deleted too. Archive it with 'expenses archive {}' instead, or pass --force",
+                        name, name
+                    )));
+                }
+
+                delete_expense(conn, name)?;
+            }
+            Commands::Pause { name, from, until } => {
+                let Some(expense) = get_expense_by_name(conn, name)? else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                };
+                let from = match from {
+                    Some(from) => parse_date(from)?,
+                    None => Local::now().date_naive(),
+                };
+                let until = until.as_deref().map(parse_date).transpose()?;
+                if until.is_some_and(|until| until < from) {
+                    return Err(color_eyre::Report::msg(
+                        "the pause cannot end before it starts",
+                    ));
+                }
+                let overlaps = expense.pauses.iter().any(|pause| {
+                    pause.start_date <= until.unwrap_or(NaiveDate::MAX)
+                        && from <= pause.end_date.unwrap_or(NaiveDate::MAX)
+                });
+                if overlaps {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense {} is already paused during that time",
+                        name
+                    )));
+                }
+
+                add_pause(conn, expense.id, from, until)?;
+            }
+            Commands::Resume { name } => {
+                let Some(expense) = get_expense_by_name(conn, name)? else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                };
+                let today = Local::now().date_naive();
+                let Some(pause) = expense
+                    .pauses
+                    .iter()
+                    .find(|pause| pause.end_date.is_none_or(|end_date| end_date >= today))
+                else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense {} is not paused",
+                        name
+                    )));
+                };
+
+                // Due dates skipped so far stay skipped.
+                if pause.start_date < today {
+                    update_pause(
+                        conn,
+                        &Pause {
+                            end_date: today.pred_opt(),
+                            ..pause.clone()
+                        },
+                    )?;
+                } else {
+                    delete_pause(conn, pause.id)?;
+                }
+            }
+            Commands::Archive { name } | Commands::Unarchive { name } => {
+                let Some(expense) = get_expense_by_name(conn, name)? else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                };
+                let archive = matches!(self.command, Commands::Archive { .. });
+                if archive == expense.archived_at.is_some() {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense {} is {}",
+                        name,
+                        if archive {
+                            "already archived"
+                        } else {
+                            "not archived"
+                        }
+                    )));
+                }
+
+                archive_expense(conn, expense.id, archive.then(chrono::Utc::now))?;
+            }
+            Commands::Show { name, payments } => {
+                let Some(expense) = get_expense_by_name(conn, name)? else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                };
+                let all_payments = get_payments(conn, Some(name))?;
+
+                let fields = describe_expense(
+                    &expense,
+                    get_splits(conn)?.get(&expense.name),
+                    &all_payments,
+                    &get_calendar(conn)?,
+                );
+                let width = fields
+                    .iter()
+                    .map(|(label, _)| label.len())
+                    .max()
+                    .unwrap_or(0);
+                for (label, value) in fields {
+                    println!(
+                        "{:<width$}  {}",
+                        format!("{label}:"),
+                        value,
+                        width = width + 1
+                    );
+                }
+
+                let mut recent = all_payments;
+                recent.sort_by_key(|p| std::cmp::Reverse((p.paid_at, p.id)));
+                recent.truncate(*payments);
+                if recent.is_empty() {
+                    println!("\nNo payments yet");
+                } else {
+                    println!("\nLatest payments:");
+                    print_rows(&generate_history_rows(&recent), Format::Table)?;
+                }
+            }
+            Commands::History {
+                name,
+                since,
+                until,
+                sort,
+                reverse,
+                format,
+            } => {
+                let since = since.as_deref().map(parse_date).transpose()?;
+                let until = until.as_deref().map(parse_date).transpose()?;
+
+                if let Some(name) = name
+                    && get_expense_by_name(conn, name)?.is_none()
+                {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                }
+
+                let payments = get_payments(conn, name.as_deref())?;
+                let mut rows: Vec<_> = generate_history_rows(&payments)
+                    .into_iter()
+                    .filter(|row| since.is_none_or(|since| row.paid_at >= since))
+                    .filter(|row| until.is_none_or(|until| row.paid_at <= until))
+                    .collect();
+
+                match sort {
+                    HistorySort::PaidAt => {}
+                    HistorySort::DueDate => rows.sort_by_key(|row| row.due_date),
+                    HistorySort::Name => rows.sort_by(|a, b| a.expense_name.cmp(b.expense_name)),
+                    HistorySort::Amount => {
+                        rows.sort_by_key(|row| row.payment.amount.map(|m| (m.currency, m.amount)))
+                    }
+                    HistorySort::Delay => rows.sort_by_key(|row| -row.days_early),
+                }
+                if *reverse {
+                    rows.reverse();
+                }
+
+                print_rows(&rows, *format)?;
+            }
+            Commands::Overdue { name, format } => {
+                let expenses: Vec<Expense> = match name {
+                    Some(name) => {
+                        let Some(expense) = get_expense_by_name(conn, name)? else {
+                            return Err(color_eyre::Report::msg(format!(
+                                "expense with name {} does not exist",
+                                name
+                            )));
+                        };
+                        vec![expense]
+                    }
+                    None => get_entries(conn)?
+                        .into_iter()
+                        .map(|(expense, _)| expense)
+                        .filter(|expense| !expense.income)
+                        .collect(),
+                };
+                let payments = get_payments(conn, name.as_deref())?;
+                let rows = generate_overdue_rows(&expenses, &payments, &get_calendar(conn)?);
+
+                print_rows(&rows, *format)?;
+            }
+            Commands::Forecast {
+                until,
+                base,
+                format,
+            } => {
+                let until = parse_date(until)?;
+                if until < Local::now().date_naive() {
+                    return Err(color_eyre::Report::msg(format!(
+                        "{} is in the past, the forecast starts today",
+                        until
+                    )));
+                }
+
+                let expenses: Vec<Expense> = get_entries(conn)?
+                    .into_iter()
+                    .map(|(expense, _)| expense)
+                    .filter(|expense| !expense.income)
+                    .collect();
+                let payments = get_payments(conn, None)?;
+                let rows =
+                    generate_forecast_rows(&expenses, &payments, &get_calendar(conn)?, until);
+
+                // Totals are computed upfront, so that a missing exchange rate fails before any output.
+                let mut weekly = BTreeMap::new();
+                let mut monthly = BTreeMap::new();
+                if format.is_human_readable() {
+                    for row in &rows {
+                        let Some(money) = row.amount else {
+                            continue;
+                        };
+                        let money = match base {
+                            Some(base) => convert(conn, money, *base, row.due_date)?,
+                            None => money,
+                        };
+                        let week = row.due_date.week(Weekday::Mon).first_day().to_string();
+                        let month = row.due_date.format("%Y-%m").to_string();
+
+                        *weekly
+                            .entry((week, money.currency))
+                            .or_insert(Decimal::ZERO) += money.amount.0;
+                        *monthly
+                            .entry((month, money.currency))
+                            .or_insert(Decimal::ZERO) += money.amount.0;
+                    }
+                }
+
+                print_rows(&rows, *format)?;
+                if format.is_human_readable() {
+                    println!("\nPer week, starting on Monday:");
+                    print_rows(&to_total_rows(weekly), *format)?;
+                    println!("\nPer month:");
+                    print_rows(&to_total_rows(monthly), *format)?;
+
+                    let missing = rows.iter().filter(|row| row.amount.is_none()).count();
+                    if missing > 0 {
+                        println!(
+                            "\nDue dates left out of the totals for lacking an amount: {}",
+                            missing
+                        );
+                    }
+                }
+            }
+            Commands::Report {
+                by,
+                category,
+                base,
+                format,
+            } => {
+                let categories = get_expense_categories(conn)?;
+                let payments: Vec<Payment> = get_expense_payments(conn)?
+                    .into_iter()
+                    .filter(|payment| {
+                        category.as_deref().is_none_or(|category| {
+                            categories
+                                .get(&payment.expense_name)
+                                .is_some_and(|c| c.eq_ignore_ascii_case(category.trim()))
+                        })
+                    })
+                    .collect();
+
+                let mut paid = Vec::new();
+                for payment in &payments {
+                    let Some(money) = payment.amount else {
+                        continue;
+                    };
+                    let paid_at = payment.paid_at.with_timezone(&Local).date_naive();
+                    let money = match base {
+                        Some(base) => convert(conn, money, *base, paid_at)?,
+                        None => money,
+                    };
+                    paid.push((paid_at, money));
+                }
+
+                print_rows(&generate_report_rows(&paid, *by), *format)?;
+
+                let missing = payments.len() - paid.len();
+                if missing > 0 && format.is_human_readable() {
+                    println!(
+                        "\nPayments left out of the report for lacking an amount: {}",
+                        missing
+                    );
+                }
+            }
+            Commands::Balance {
+                month,
+                base,
+                format,
+            } => {
+                let from = match month {
+                    Some(month) => parse_month(month)?,
+                    None => Local::now()
+                        .date_naive()
+                        .with_day(1)
+                        .expect("every month has a 1st"),
+                };
+                let until = from + chrono::Months::new(1) - chrono::Days::new(1);
+
+                let entries: Vec<Expense> = get_entries(conn)?
+                    .into_iter()
+                    .map(|(expense, _)| expense)
+                    .collect();
+                let rows = generate_balance_rows(
+                    &entries,
+                    &get_payments(conn, None)?,
+                    &get_calendar(conn)?,
+                    from,
+                    until,
+                    |money, date| match base {
+                        Some(base) => convert(conn, money, *base, date),
+                        None => Ok(money),
+                    },
+                )?;
+
+                if format.is_human_readable() {
+                    println!("{}", from.format("%B %Y"));
+                }
+                print_rows(&rows, *format)?;
+            }
+            Commands::Ledgers => {
+                for ledger in get_ledgers()? {
+                    println!("{ledger}");
+                }
+            }
+            Commands::Rate { command } => match command {
+                RateCommands::Set {
+                    from,
+                    to,
+                    rate,
+                    date,
+                } => {
+                    if from == to {
+                        return Err(color_eyre::Report::msg(
+                            "please choose two different currencies",
+                        ));
+                    }
+                    if rate.0.is_zero() {
+                        return Err(color_eyre::Report::msg("exchange rate must be positive"));
+                    }
+                    let date = match date {
+                        Some(date) => parse_date(date)?,
+                        None => Local::now().date_naive(),
+                    };
+
+                    set_exchange_rate(conn, *from, *to, *rate, date)?;
+                }
+                RateCommands::List => {
+                    let rates = get_exchange_rates(conn)?;
+                    let mut builder = tabled::builder::Builder::new();
+                    builder.push_record(["from", "to", "rate", "effective_date"]);
+                    for rate in rates {
+                        builder.push_record([
+                            rate.from_currency.to_string(),
+                            rate.to_currency.to_string(),
+                            rate.rate.to_string(),
+                            rate.effective_date.to_string(),
+                        ]);
+                    }
+
+                    println!("{}", builder.build());
+                }
+            },
+            Commands::Import { command } => match command {
+                ImportCommands::Csv {
+                    file,
+                    mappings,
+                    dry_run,
+                } => {
+                    let rows = read_csv(std::fs::File::open(file)?, mappings)?;
+
+                    // Rows see the ones before them, so that payments can follow their expense.
+                    let tx = conn.unchecked_transaction()?;
+                    let mut imported = Vec::new();
+                    let mut errors = Vec::new();
+                    for row in &rows {
+                        let result = if row.get("name").is_none() {
+                            Err(color_eyre::Report::msg("name is missing"))
+                        } else if row.is_payment() {
+                            import_payment(&tx, row)
+                        } else {
+                            import_expense(&tx, row)
+                        };
+
+                        match result {
+                            Ok(row) => imported.push(row),
+                            Err(e) => errors.push(format!("line {}: {}", row.line, e)),
+                        }
+                    }
+
+                    if !errors.is_empty() {
+                        return Err(color_eyre::Report::msg(format!(
+                            "nothing was imported, as {} rows are invalid:\n{}",
+                            errors.len(),
+                            errors.join("\n")
+                        )));
+                    }
+
+                    let payments = imported.iter().filter(|row| row.kind == "payment").count();
+                    if *dry_run {
+                        print_rows(&imported, Format::Table)?;
+                        println!(
+                            "{} expenses and {} payments would be imported",
+                            imported.len() - payments,
+                            payments
+                        );
+                    } else {
+                        tx.commit()?;
+                        println!(
+                            "imported {} expenses and {} payments",
+                            imported.len() - payments,
+                            payments
+                        );
+                    }
+                }
+            },
+            Commands::Member { command } => match command {
+                MemberCommands::Add { name } => {
+                    let Some(name) = non_empty(name) else {
+                        return Err(color_eyre::Report::msg("member names cannot be empty"));
+                    };
+
+                    let add_member_result = add_member(conn, &name);
+
+                    if let Err(Error::SqliteFailure(ffi::Error { extended_code, .. }, _)) =
+                        add_member_result
+                        && extended_code == 2067
+                    {
+                        return Err(color_eyre::Report::msg(format!(
+                            "member {} already exists",
+                            name
+                        )));
+                    }
+
+                    add_member_result?;
+                }
+                MemberCommands::Delete { name } => {
+                    let name = find_registered("member", &get_members(conn)?, name)?;
+
+                    match delete_member(conn, &name) {
+                        Err(Error::SqliteFailure(
+                            ffi::Error {
+                                extended_code: 787, ..
+                            },
+                            _,
+                        )) => {
+                            return Err(color_eyre::Report::msg(format!(
+                                "member {} still has payments or shares an expense",
+                                name
+                            )));
+                        }
+                        result => {
+                            result?;
+                        }
+                    }
+                }
+                MemberCommands::List => {
+                    for member in get_members(conn)? {
+                        println!("{member}");
+                    }
+                }
+            },
+            Commands::Account { command } => match command {
+                AccountCommands::Add { name } => {
+                    let Some(name) = non_empty(name) else {
+                        return Err(color_eyre::Report::msg("account names cannot be empty"));
+                    };
+
+                    let add_account_result = add_account(conn, &name);
+
+                    if let Err(Error::SqliteFailure(ffi::Error { extended_code, .. }, _)) =
+                        add_account_result
+                        && extended_code == 2067
+                    {
+                        return Err(color_eyre::Report::msg(format!(
+                            "account {} already exists",
+                            name
+                        )));
+                    }
+
+                    add_account_result?;
+                }
+                AccountCommands::Delete { name } => {
+                    let name = find_registered("account", &get_accounts(conn)?, name)?;
+
+                    match delete_account(conn, &name) {
+                        Err(Error::SqliteFailure(
+                            ffi::Error {
+                                extended_code: 787, ..
+                            },
+                            _,
+                        )) => {
+                            return Err(color_eyre::Report::msg(format!(
+                                "account {} is still used by expenses or payments",
+                                name
+                            )));
+                        }
+                        result => {
+                            result?;
+                        }
+                    }
+                }
+                AccountCommands::List => {
+                    for account in get_accounts(conn)? {
+                        println!("{account}");
+                    }
+                }
+            },
+            Commands::Accounts { days, base, format } => {
+                let until = Local::now().date_naive() + chrono::Days::new(u64::from(*days));
+
+                let expenses: Vec<Expense> = get_entries(conn)?
+                    .into_iter()
+                    .map(|(expense, _)| expense)
+                    .filter(|expense| !expense.income)
+                    .collect();
+                let forecast = generate_forecast_rows(
+                    &expenses,
+                    &get_payments(conn, None)?,
+                    &get_calendar(conn)?,
+                    until,
+                );
+                let accounts = get_accounts(conn)?;
+                let rows = generate_account_rows(
+                    &accounts,
+                    &expenses,
+                    &forecast,
+                    |money, date| match base {
+                        Some(base) => convert(conn, money, *base, date),
+                        None => Ok(money),
+                    },
+                )?;
+
+                print_rows(&rows, *format)?;
+            }
+            Commands::Split {
+                name,
+                rule,
+                members,
+                clear,
+            } => {
+                let Some(expense) = get_expense_by_name(conn, name)? else {
+                    return Err(color_eyre::Report::msg(format!(
+                        "expense with name {} does not exist",
+                        name
+                    )));
+                };
+
+                if *clear {
+                    if !delete_split(conn, expense.id)? {
+                        return Err(color_eyre::Report::msg(format!(
+                            "expense {} is not split",
+                            name
+                        )));
+                    }
+                } else {
+                    let split = parse_split(conn, *rule, members)?;
+
+                    let tx = conn.unchecked_transaction()?;
+                    set_split(&tx, expense.id, &split)?;
+                    tx.commit()?;
+                }
+            }
+            Commands::Settle {
+                since,
+                until,
+                base,
+                format,
+            } => {
+                let since = since.as_deref().map(parse_date).transpose()?;
+                let until = until.as_deref().map(parse_date).transpose()?;
+                let splits = get_splits(conn)?;
+
+                let mut balances = BTreeMap::new();
+                let mut without_payer = 0;
+                for payment in get_expense_payments(conn)? {
+                    let paid_on = payment.paid_at.with_timezone(&Local).date_naive();
+                    let Some(split) = splits.get(&payment.expense_name) else {
+                        continue;
+                    };
+                    if since.is_some_and(|since| paid_on < since)
+                        || until.is_some_and(|until| paid_on > until)
+                    {
+                        continue;
+                    }
+                    let (Some(paid_by), Some(amount)) = (payment.paid_by, payment.amount) else {
+                        without_payer += 1;
+                        continue;
+                    };
+                    let amount = match base {
+                        Some(base) => convert(conn, amount, *base, paid_on)?,
+                        None => amount,
+                    };
+
+                    *balances
+                        .entry((amount.currency, paid_by))
+                        .or_insert(Decimal::ZERO) += amount.amount.0;
+                    for (member, share) in get_shares(split, amount.amount.0) {
+                        *balances
+                            .entry((amount.currency, member.to_string()))
+                            .or_insert(Decimal::ZERO) -= share;
+                    }
+                }
+
+                let rows = generate_settlement_rows(&balances);
+                if rows.is_empty() && format.is_human_readable() {
+                    println!("Everyone is settled up");
+                } else {
+                    print_rows(&rows, *format)?;
+                }
+                if without_payer > 0 && format.is_human_readable() {
+                    println!(
+                        "\nPayments left out of the settlement for lacking a payer or an amount: {}",
+                        without_payer
+                    );
+                }
+            }
+            Commands::Budget { command, format } => match command {
+                None => {
+                    let budgets = get_budgets(conn)?;
+                    let rows = generate_budget_rows(
+                        &budgets,
+                        &get_expense_payments(conn)?,
+                        &get_expense_categories(conn)?,
+                        Local::now().date_naive(),
+                        |money, currency, date| convert(conn, money, currency, date),
+                    )?;
+
+                    print_rows(&rows, *format)?;
+                }
+                Some(BudgetCommands::Set {
+                    amount,
+                    category,
+                    expense,
+                    period,
+                    currency,
+                }) => {
+                    let Some(currency) = currency else {
+                        return Err(color_eyre::Report::msg(
+                            "please specify the currency of the limit with --currency or EXPENSES_CURRENCY",
+                        ));
+                    };
+                    let target = to_budget_target(category, expense)?;
+                    let limit = Money {
+                        amount: *amount,
+                        currency: *currency,
+                    };
+
+                    if !set_budget(conn, &target, *period, limit)? {
+                        return Err(color_eyre::Report::msg(format!(
+                            "expense with name {} does not exist",
+                            expense.as_deref().unwrap_or_default()
+                        )));
+                    }
+                }
+                Some(BudgetCommands::Delete {
+                    category,
+                    expense,
+                    period,
+                }) => {
+                    let target = to_budget_target(category, expense)?;
+
+                    if !delete_budget(conn, &target, *period)? {
+                        return Err(color_eyre::Report::msg(format!(
+                            "there is no {} budget for {}",
+                            period.as_adjective(),
+                            target
+                        )));
+                    }
+                }
+            },
+            Commands::Holiday { command } => match command {
+                HolidayCommands::Import { file } => {
+                    let is_ics = file
+                        .extension()
+                        .is_some_and(|extension| extension.eq_ignore_ascii_case("ics"));
+                    let holidays = if is_ics {
+                        parse_ics(&std::fs::read_to_string(file)?)?
+                    } else {
+                        parse_csv(std::fs::File::open(file)?)?
+                    };
+
+                    let tx = conn.unchecked_transaction()?;
+                    for holiday in &holidays {
+                        set_holiday(&tx, holiday)?;
+                    }
+                    tx.commit()?;
+
+                    println!("imported {} holidays", holidays.len());
+                }
+                HolidayCommands::Add { date, name } => {
+                    set_holiday(
+                        conn,
+                        &Holiday {
+                            date: parse_date(date)?,
+                            name: name.clone(),
+                        },
+                    )?;
+                }
+                HolidayCommands::Delete { date } => {
+                    if !delete_holiday(conn, parse_date(date)?)? {
+                        return Err(color_eyre::Report::msg(format!(
+                            "no holiday registered on {}",
+                            date
+                        )));
+                    }
+                }
+                HolidayCommands::List => {
+                    let mut builder = tabled::builder::Builder::new();
+                    builder.push_record(["date", "name"]);
+                    for holiday in get_holidays(conn)? {
+                        builder.push_record([holiday.date.to_string(), holiday.name]);
+                    }
+
+                    println!("{}", builder.build());
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(conn: &Connection, args: &[&str]) -> Result<()> {
+        Cli::try_parse_from([&["expenses"], args].concat())?.run(conn)
+    }
+
+    #[test]
+    fn test_edit_clears_end() {
+        let conn = crate::migrations::open_in_memory();
+        run(
+            &conn,
+            &["add", "Sofa", "-d", "2026-01-05", "--installments", "12"],
+        )
+        .unwrap();
+        run(
+            &conn,
+            &["add", "Course", "-d", "2026-02-01", "--until", "2026-06-30"],
+        )
+        .unwrap();
+
+        run(&conn, &["edit", "Sofa", "--no-installments"]).unwrap();
+        run(&conn, &["edit", "Course", "--no-until"]).unwrap();
+        let sofa = get_expense_by_name(&conn, "Sofa").unwrap().unwrap();
+        let course = get_expense_by_name(&conn, "Course").unwrap().unwrap();
+        assert_eq!(sofa.installments, None);
+        assert_eq!(course.until, None);
+
+        assert!(
+            run(
+                &conn,
+                &["edit", "Course", "--until", "2026-06-30", "--no-until"]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_import_checks_as_add() {
+        let conn = crate::migrations::open_in_memory();
+        run(&conn, &["add", "Rent", "-d", "2026-01-05"]).unwrap();
+        let file = "name,date,once,installments,until\n\
+                    Inspection,2026-03-15,yes,,\n\
+                    Rent,2026-01-05,,,\n\
+                    Sofa,2026-01-05,,0,\n\
+                    Course,2026-02-01,yes,,2026-06-30\n";
+        let rows = read_csv(file.as_bytes(), &[]).unwrap();
+
+        import_expense(&conn, &rows[0]).unwrap();
+        let inspection = get_expense_by_name(&conn, "Inspection").unwrap().unwrap();
+        assert_eq!(inspection.installments, Some(1));
+        for row in &rows[1..] {
+            assert!(import_expense(&conn, row).is_err());
+        }
+        assert!(run(&conn, &["add", "Rent", "-d", "2026-02-05"]).is_err());
     }
 }
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use color_eyre::Result;
+
+/// Fields the columns of an imported file can be mapped to. Rows with a `paid_at` are payments of
+/// the expense called `name`, the others new expenses.
+pub(crate) const FIELDS: &[&str] = &[
+    "name",
+    "period",
+    "date",
+    "month_end",
+    "business_day",
+    "once",
+    "installments",
+    "until",
+    "income",
+    "amount",
+    "currency",
+    "variable",
+    "category",
+    "tags",
+    "payee",
+    "reference",
+    "url",
+    "notes",
+    "account",
+    "paid_at",
+    "for",
+    "method",
+    "confirmation",
+    "note",
+    "by",
+];
+
+/// A line of an imported file, with its values by field.
+#[derive(Debug)]
+pub(crate) struct ImportRow {
+    pub(crate) line: u64,
+    values: HashMap<&'static str, String>,
+}
+
+impl ImportRow {
+    /// The trimmed value of a field, left out when empty.
+    pub(crate) fn get(&self, field: &str) -> Option<&str> {
+        self.values
+            .get(field)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    pub(crate) fn is_payment(&self) -> bool {
+        self.get("paid_at").is_some()
+    }
+}
+
+fn to_field(name: &str) -> Option<&'static str> {
+    let name = name.trim().to_lowercase().replace([' ', '-'], "_");
+
+    FIELDS.iter().find(|field| **field == name).copied()
+}
+
+/// Parses a `COLUMN=FIELD` mapping, as given to `--map`.
+pub(crate) fn parse_mapping(mapping: &str) -> Result<(String, &'static str), String> {
+    let Some((column, field)) = mapping.rsplit_once('=') else {
+        return Err(format!(
+            "invalid mapping: {mapping}. Expecting something like 'Description=name'"
+        ));
+    };
+    let Some(field) = to_field(field) else {
+        return Err(format!(
+            "unknown field: {field}. Expecting one of {}",
+            FIELDS.join(", ")
+        ));
+    };
+
+    Ok((column.trim().to_string(), field))
+}
+
+/// Reads a CSV file with a header row. Columns are matched to fields by `mappings` or, failing
+/// that, by their own name. Columns matching no field are ignored.
+pub(crate) fn read_csv<R: std::io::Read>(
+    reader: R,
+    mappings: &[(String, &'static str)],
+) -> Result<Vec<ImportRow>> {
+    let mut reader = csv::Reader::from_reader(reader);
+
+    let headers = reader.headers()?.clone();
+    for (column, _) in mappings {
+        if !headers.iter().any(|header| header.trim() == column) {
+            return Err(color_eyre::Report::msg(format!(
+                "there is no column named {} in the file",
+                column
+            )));
+        }
+    }
+
+    let mut fields: Vec<Option<&'static str>> = Vec::new();
+    for header in &headers {
+        let field = mappings
+            .iter()
+            .find(|(column, _)| header.trim() == column)
+            .map(|(_, field)| *field)
+            .or_else(|| to_field(header));
+        if let Some(field) = field
+            && fields.contains(&Some(field))
+        {
+            return Err(color_eyre::Report::msg(format!(
+                "more than one column is mapped to {}",
+                field
+            )));
+        }
+
+        fields.push(field);
+    }
+    if !fields.contains(&Some("name")) {
+        return Err(color_eyre::Report::msg(
+            "no column is mapped to name. Map one with --map '<COLUMN>=name'",
+        ));
+    }
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record?;
+            let values = fields
+                .iter()
+                .zip(record.iter())
+                .filter_map(|(field, value)| field.map(|field| (field, value.to_string())))
+                .collect();
+
+            Ok(ImportRow {
+                line: record.position().map_or(0, |position| position.line()),
+                values,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_csv() {
+        let file = "Description,Date,Period,Value,Comments\n\
+                    Rent,2026-01-05,monthly,1500,\n\
+                    Rent,,,1500,\"paid, late\"\n";
+        let mappings = [
+            parse_mapping("Description=name").unwrap(),
+            parse_mapping("Value=amount").unwrap(),
+        ];
+
+        let rows = read_csv(file.as_bytes(), &mappings).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].get("name"), Some("Rent"));
+        assert_eq!(rows[0].get("date"), Some("2026-01-05"));
+        assert_eq!(rows[0].get("amount"), Some("1500"));
+        assert_eq!(rows[0].get("notes"), None);
+        assert_eq!(rows[1].get("period"), None);
+
+        assert!(parse_mapping("Description").is_err());
+        assert!(parse_mapping("Description=title").is_err());
+        assert!(read_csv(file.as_bytes(), &[]).is_err());
+        assert!(read_csv(file.as_bytes(), &[parse_mapping("Missing=name").unwrap()]).is_err());
+    }
+}
@@ -2,18 +2,24 @@ use clap::Parser;
 use color_eyre::Result;
 use rusqlite::Connection;
 
+mod calendar;
 mod cli;
+mod import;
+mod migrations;
 mod model;
+mod output;
 mod queries;
+mod recurrence;
 mod utils;
 
 fn main() -> Result<()> {
-    let conn = Connection::open(queries::get_data_path())?;
+    let cli = cli::Cli::parse();
+
+    let mut conn = Connection::open(cli.data_path()?)?;
     conn.execute("PRAGMA foreign_keys = ON;", ())?;
 
-    _ = queries::create_tables(&conn);
+    migrations::migrate(&mut conn)?;
 
-    let cli = cli::Cli::parse();
     cli.run(&conn)?;
 
     Ok(())
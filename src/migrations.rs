@@ -0,0 +1,272 @@
+use color_eyre::eyre::Result;
+use rusqlite::Connection;
+
+/// Schema changes, in order. The database's `user_version` is the amount of them already applied.
+///
+/// Never edit or reorder an entry once it has been released: append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: Schema shipped up to v0.1.2.
+    "CREATE TABLE expense (
+         id                  INTEGER PRIMARY KEY,
+         created_at          TEXT NOT NULL,
+         name                TEXT UNIQUE NOT NULL,
+         periodicity         TEXT NOT NULL,
+         due_date_reference  TEXT NOT NULL
+     );
+     CREATE TABLE payment (
+         id                  INTEGER PRIMARY KEY,
+         created_at          TEXT NOT NULL,
+         paid_at             TEXT NOT NULL,
+         expense_name        TEXT NOT NULL,
+         due_date_of_expense TEXT NOT NULL,
+         FOREIGN KEY (expense_name) REFERENCES expense(name) ON DELETE CASCADE ON UPDATE CASCADE
+     );",
+    // 2: Amounts, stored as decimal text.
+    "ALTER TABLE expense ADD COLUMN amount TEXT;
+     ALTER TABLE payment ADD COLUMN amount TEXT;",
+    // 3: Currencies and exchange rates.
+    "ALTER TABLE expense ADD COLUMN currency TEXT;
+     ALTER TABLE payment ADD COLUMN currency TEXT;
+     CREATE TABLE exchange_rate (
+         id                  INTEGER PRIMARY KEY,
+         from_currency       TEXT NOT NULL,
+         to_currency         TEXT NOT NULL,
+         rate                TEXT NOT NULL,
+         effective_date      TEXT NOT NULL,
+         UNIQUE (from_currency, to_currency, effective_date)
+     );",
+    // 4: General recurrence rules, stored as RRULEs. 'Quarterly' used to mean every 4 months,
+    // so it is kept that way for existing expenses.
+    "ALTER TABLE expense RENAME COLUMN periodicity TO recurrence;
+     UPDATE expense SET recurrence = CASE recurrence
+         WHEN 'Weekly' THEN 'FREQ=WEEKLY'
+         WHEN 'Monthly' THEN 'FREQ=MONTHLY'
+         WHEN 'Bimonthly' THEN 'FREQ=MONTHLY;INTERVAL=2'
+         WHEN 'Trimonthly' THEN 'FREQ=MONTHLY;INTERVAL=3'
+         WHEN 'Quarterly' THEN 'FREQ=MONTHLY;INTERVAL=4'
+         WHEN 'Biannual' THEN 'FREQ=MONTHLY;INTERVAL=6'
+         ELSE recurrence
+     END;",
+    // 5: What to do with due days that do not exist in some months.
+    "ALTER TABLE expense ADD COLUMN month_end TEXT NOT NULL DEFAULT 'clamp';",
+    // 6: Business-day adjustment of due dates, and the holidays it takes into account.
+    "ALTER TABLE expense ADD COLUMN business_day TEXT NOT NULL DEFAULT 'none';
+     CREATE TABLE holiday (
+         date TEXT PRIMARY KEY NOT NULL,
+         name TEXT NOT NULL
+     );",
+    // 7: One-off expenses and installment plans.
+    "ALTER TABLE expense ADD COLUMN installments INTEGER;
+     ALTER TABLE expense ADD COLUMN until TEXT;",
+    // 8: Pausing and archiving expenses.
+    "ALTER TABLE expense ADD COLUMN archived_at TEXT;
+     CREATE TABLE pause (
+         id          INTEGER PRIMARY KEY,
+         expense_id  INTEGER NOT NULL,
+         start_date  TEXT NOT NULL,
+         end_date    TEXT,
+         FOREIGN KEY (expense_id) REFERENCES expense(id) ON DELETE CASCADE
+     );",
+    // 9: Categories and tags.
+    "CREATE TABLE category (
+         id    INTEGER PRIMARY KEY,
+         name  TEXT UNIQUE NOT NULL COLLATE NOCASE
+     );
+     ALTER TABLE expense ADD COLUMN category_id INTEGER REFERENCES category(id) ON DELETE SET NULL;
+     CREATE TABLE tag (
+         id    INTEGER PRIMARY KEY,
+         name  TEXT UNIQUE NOT NULL COLLATE NOCASE
+     );
+     CREATE TABLE expense_tag (
+         expense_id  INTEGER NOT NULL,
+         tag_id      INTEGER NOT NULL,
+         PRIMARY KEY (expense_id, tag_id),
+         FOREIGN KEY (expense_id) REFERENCES expense(id) ON DELETE CASCADE,
+         FOREIGN KEY (tag_id) REFERENCES tag(id) ON DELETE CASCADE
+     );",
+    // 10: Free-form details of expenses and payments.
+    "ALTER TABLE expense ADD COLUMN payee TEXT;
+     ALTER TABLE expense ADD COLUMN reference TEXT;
+     ALTER TABLE expense ADD COLUMN url TEXT;
+     ALTER TABLE expense ADD COLUMN notes TEXT;
+     ALTER TABLE payment ADD COLUMN method TEXT;
+     ALTER TABLE payment ADD COLUMN confirmation TEXT;
+     ALTER TABLE payment ADD COLUMN note TEXT;",
+    // 11: Expenses whose amount varies between bills.
+    "ALTER TABLE expense ADD COLUMN estimate TEXT;",
+    // 12: Spending limits per category or expense.
+    "CREATE TABLE budget (
+        id INTEGER PRIMARY KEY,
+        category_id INTEGER REFERENCES category(id) ON DELETE CASCADE,
+        expense_id INTEGER REFERENCES expense(id) ON DELETE CASCADE,
+        period TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        currency TEXT NOT NULL,
+        CHECK ((category_id IS NULL) <> (expense_id IS NULL)),
+        UNIQUE (category_id, period),
+        UNIQUE (expense_id, period)
+     );",
+    // 13: Income, scheduled like expenses.
+    "ALTER TABLE expense ADD COLUMN income INTEGER NOT NULL DEFAULT 0;",
+    // 14: Household members sharing expenses, and who made each payment.
+    "CREATE TABLE member (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+     );
+     CREATE TABLE split (
+        expense_id INTEGER PRIMARY KEY REFERENCES expense(id) ON DELETE CASCADE,
+        rule TEXT NOT NULL
+     );
+     CREATE TABLE split_share (
+        expense_id INTEGER NOT NULL REFERENCES split(expense_id) ON DELETE CASCADE,
+        member_id INTEGER NOT NULL REFERENCES member(id),
+        share TEXT,
+        PRIMARY KEY (expense_id, member_id)
+     );
+     ALTER TABLE payment ADD COLUMN member_id INTEGER REFERENCES member(id);",
+    // 15: Accounts expenses are paid from, by default and on each payment.
+    "CREATE TABLE account (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE COLLATE NOCASE
+     );
+     ALTER TABLE expense ADD COLUMN account_id INTEGER REFERENCES account(id);
+     ALTER TABLE payment ADD COLUMN account_id INTEGER REFERENCES account(id);",
+];
+
+fn user_version(conn: &Connection) -> Result<usize> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    Ok(usize::try_from(version)?)
+}
+
+/// Databases created before migrations existed have tables but were never versioned.
+fn is_unversioned_legacy(conn: &Connection) -> Result<bool> {
+    let tables: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'expense'",
+        [],
+        |row| row.get(0),
+    )?;
+
+    Ok(tables > 0)
+}
+
+/// Brings the database schema up to date, upgrading it in place if it was created by an older version.
+pub(crate) fn migrate(conn: &mut Connection) -> Result<()> {
+    let mut version = user_version(conn)?;
+
+    if version > MIGRATIONS.len() {
+        return Err(color_eyre::Report::msg(format!(
+            "database schema version {} is newer than the one supported by this binary ({}). Please upgrade expenses",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+
+    if version == 0 && is_unversioned_legacy(conn)? {
+        version = 1;
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) fn open_in_memory() -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.execute("PRAGMA foreign_keys = ON;", ()).unwrap();
+    migrate(&mut conn).unwrap();
+
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exactly what `create_tables` produced in v0.1.2, which never set `user_version`.
+    const V0_1_2_FIXTURE: &str = "
+        CREATE TABLE expense (
+                 id                  INTEGER PRIMARY KEY,
+                 created_at          TEXT NOT NULL,
+                 name                TEXT UNIQUE NOT NULL,
+                 periodicity         TEXT NOT NULL,
+                 due_date_reference  TEXT NOT NULL
+              );
+        CREATE TABLE payment (
+                 id                  INTEGER PRIMARY KEY,
+                 created_at          TEXT NOT NULL,
+                 paid_at             TEXT NOT NULL,
+                 expense_name        TEXT NOT NULL,
+                 due_date_of_expense TEXT NOT NULL,
+                 FOREIGN KEY (expense_name) REFERENCES expense(name) ON DELETE CASCADE ON UPDATE CASCADE
+              );
+        INSERT INTO expense (created_at, name, periodicity, due_date_reference)
+            VALUES ('2025-11-01 12:00:00+00:00', 'Internet', 'Monthly', '2025-11-05 03:00:01+00:00');
+        INSERT INTO expense (created_at, name, periodicity, due_date_reference)
+            VALUES ('2025-11-01 12:00:00+00:00', 'Insurance', 'Quarterly', '2025-11-20 03:00:01+00:00');
+        INSERT INTO payment (created_at, paid_at, expense_name, due_date_of_expense)
+            VALUES ('2025-11-04 12:00:00+00:00', '2025-11-04 12:00:00+00:00', 'Internet', '2025-11-05 03:00:01+00:00');
+    ";
+
+    #[test]
+    fn test_upgrades_v0_1_2_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(V0_1_2_FIXTURE).unwrap();
+
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), MIGRATIONS.len());
+
+        let entries = crate::queries::get_entries(&conn).unwrap();
+        assert_eq!(entries.len(), 2);
+        let (expense, payment) = &entries[0];
+        assert_eq!(expense.name, "Internet");
+        assert_eq!(expense.recurrence.to_rrule(), "FREQ=MONTHLY");
+        assert!(expense.amount.is_none());
+        assert!(payment.is_some());
+        let (expense, _) = &entries[1];
+        assert_eq!(expense.recurrence.to_rrule(), "FREQ=MONTHLY;INTERVAL=4");
+
+        // Payments with an amount can be registered to expenses from before amounts existed.
+        let due_date = expense.due_date_reference;
+        crate::queries::add_payment(
+            &conn,
+            &crate::model::NewPayment {
+                created_at: due_date,
+                paid_at: due_date,
+                expense_name: "Insurance",
+                due_date_of_expense: due_date,
+                amount: Some(crate::model::Money {
+                    amount: "120".parse().unwrap(),
+                    currency: "BRL".parse().unwrap(),
+                }),
+                method: None,
+                confirmation: None,
+                note: None,
+                paid_by: None,
+                account: None,
+            },
+        )
+        .unwrap();
+        let payments = crate::queries::get_payments(&conn, Some("Insurance")).unwrap();
+        assert_eq!(payments[0].amount.unwrap().to_string(), "120.00 BRL");
+
+        // Running again on an up to date database is a no-op.
+        migrate(&mut conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_refuses_newer_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+
+        assert!(migrate(&mut conn).is_err());
+    }
+}
@@ -4,108 +4,397 @@ use rusqlite::{
     ToSql,
     types::{FromSql, FromSqlResult, ValueRef},
 };
+use rust_decimal::Decimal;
+use serde::{Serialize, Serializer};
 use std::fmt::Display;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
-pub(crate) enum Periodicity {
-    Weekly,
-    Monthly,
-    Bimonthly,
-    Trimonthly,
-    Quarterly,
-    Biannual,
-}
-
-impl Periodicity {
-    pub fn get_row_color_on_time_left(&self, days_left: i64) -> tabled::settings::Color {
-        let (red_range, yellow_range) = match self {
-            Periodicity::Weekly => (0..2, 2..=3),
-            Periodicity::Monthly => (0..5, 5..=10),
-            Periodicity::Bimonthly => (0..10, 10..=15),
-            Periodicity::Trimonthly => (0..15, 15..=30),
-            Periodicity::Quarterly => (0..20, 20..=30),
-            Periodicity::Biannual => (0..30, 30..=60),
-        };
-
-        if red_range.contains(&days_left) {
-            return tabled::settings::Color::FG_RED;
-        } else if yellow_range.contains(&days_left) {
-            return tabled::settings::Color::FG_YELLOW;
+use crate::calendar::BusinessDayRule;
+use crate::recurrence::{MonthEndPolicy, Recurrence};
+
+/// Monetary value, kept as an exact decimal and stored as text so no precision is lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Amount(pub(crate) Decimal);
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2}", self.0)
+    }
+}
+
+impl FromStr for Amount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = Decimal::from_str_exact(s.trim())
+            .map_err(|_| format!("invalid amount: {s}. Expecting something like '123.45'"))?;
+
+        if value.is_sign_negative() {
+            return Err(format!("invalid amount: {s}. Amounts cannot be negative"));
+        }
+
+        Ok(Amount(value.normalize()))
+    }
+}
+
+impl FromSql for Amount {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let text = value.as_str()?;
+
+        Decimal::from_str_exact(text)
+            .map(Amount)
+            .map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for Amount {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.0.to_string()))
+    }
+}
+
+/// ISO 4217 currency code, such as `BRL`, `EUR` or `USD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) struct Currency([u8; 3]);
+
+impl Currency {
+    pub(crate) fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("currency codes are always ascii")
+    }
+}
+
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_ascii_uppercase();
+
+        match code.as_bytes() {
+            [a, b, c] if code.bytes().all(|b| b.is_ascii_uppercase()) => Ok(Currency([*a, *b, *c])),
+            _ => Err(format!(
+                "invalid currency: {s}. Expecting an ISO 4217 code such as 'BRL', 'EUR' or 'USD'"
+            )),
         }
+    }
+}
 
-        tabled::settings::Color::empty()
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
     }
 }
 
-impl Display for Periodicity {
+impl FromSql for Currency {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|_| rusqlite::types::FromSqlError::InvalidType)
+    }
+}
+
+impl ToSql for Currency {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(ValueRef::Text(
+            &self.0,
+        )))
+    }
+}
+
+/// An amount tied to the currency it is expressed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Money {
+    pub(crate) amount: Amount,
+    pub(crate) currency: Currency,
+}
+
+impl Money {
+    /// Builds the value back from its two nullable database columns.
+    pub(crate) fn from_columns(amount: Option<Amount>, currency: Option<Currency>) -> Option<Self> {
+        amount
+            .zip(currency)
+            .map(|(amount, currency)| Money { amount, currency })
+    }
+}
+
+impl Display for Money {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.amount, self.currency)
+    }
+}
+
+/// How the next amount of an expense that varies between bills is estimated from its payments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Estimate {
+    /// The amount last paid.
+    Last,
+    /// The average of the latest payments.
+    Average,
+    /// The amount paid for the same month of the previous year.
+    SameMonth,
+}
+
+impl Estimate {
+    fn as_str(&self) -> &'static str {
         match self {
-            Periodicity::Weekly => f.write_str("Weekly"),
-            Periodicity::Monthly => f.write_str("Monthly"),
-            Periodicity::Bimonthly => f.write_str("Bimonthly"),
-            Periodicity::Trimonthly => f.write_str("Trimonthly"),
-            Periodicity::Quarterly => f.write_str("Quarterly"),
-            Periodicity::Biannual => f.write_str("Biannual"),
+            Estimate::Last => "last",
+            Estimate::Average => "average",
+            Estimate::SameMonth => "same-month",
         }
     }
 }
 
-impl FromSql for Periodicity {
-    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+impl Display for Estimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromSql for Estimate {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         match value.as_str() {
-            Ok("Weekly") => FromSqlResult::Ok(Periodicity::Weekly),
-            Ok("Monthly") => FromSqlResult::Ok(Periodicity::Monthly),
-            Ok("Bimonthly") => FromSqlResult::Ok(Periodicity::Bimonthly),
-            Ok("Trimonthly") => FromSqlResult::Ok(Periodicity::Trimonthly),
-            Ok("Quarterly") => FromSqlResult::Ok(Periodicity::Quarterly),
-            Ok("Biannual") => FromSqlResult::Ok(Periodicity::Biannual),
+            Ok("last") => FromSqlResult::Ok(Estimate::Last),
+            Ok("average") => FromSqlResult::Ok(Estimate::Average),
+            Ok("same-month") => FromSqlResult::Ok(Estimate::SameMonth),
             _ => FromSqlResult::Err(rusqlite::types::FromSqlError::InvalidType),
         }
     }
 }
 
-impl ToSql for Periodicity {
+impl ToSql for Estimate {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(ValueRef::Text(
+            self.as_str().as_bytes(),
+        )))
+    }
+}
+
+/// How long the spending limit of a budget lasts before starting over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum BudgetPeriod {
+    Month,
+    Year,
+}
+
+impl BudgetPeriod {
+    fn as_str(&self) -> &'static str {
         match self {
-            Periodicity::Weekly => rusqlite::Result::Ok(rusqlite::types::ToSqlOutput::Borrowed(
-                ValueRef::Text("Weekly".as_bytes()),
-            )),
-            Periodicity::Monthly => rusqlite::Result::Ok(rusqlite::types::ToSqlOutput::Borrowed(
-                ValueRef::Text("Monthly".as_bytes()),
-            )),
+            BudgetPeriod::Month => "month",
+            BudgetPeriod::Year => "year",
+        }
+    }
 
-            Periodicity::Bimonthly => rusqlite::Result::Ok(rusqlite::types::ToSqlOutput::Borrowed(
-                ValueRef::Text("Bimonthly".as_bytes()),
-            )),
-            Periodicity::Trimonthly => rusqlite::Result::Ok(
-                rusqlite::types::ToSqlOutput::Borrowed(ValueRef::Text("Trimonthly".as_bytes())),
-            ),
-            Periodicity::Quarterly => rusqlite::Result::Ok(rusqlite::types::ToSqlOutput::Borrowed(
-                ValueRef::Text("Quarterly".as_bytes()),
-            )),
-            Periodicity::Biannual => rusqlite::Result::Ok(rusqlite::types::ToSqlOutput::Borrowed(
-                ValueRef::Text("Biannual".as_bytes()),
-            )),
+    /// "monthly" or "yearly", for messages.
+    pub(crate) fn as_adjective(&self) -> &'static str {
+        match self {
+            BudgetPeriod::Month => "monthly",
+            BudgetPeriod::Year => "yearly",
         }
     }
 }
 
+impl Display for BudgetPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromSql for BudgetPeriod {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("month") => FromSqlResult::Ok(BudgetPeriod::Month),
+            Ok("year") => FromSqlResult::Ok(BudgetPeriod::Year),
+            _ => FromSqlResult::Err(rusqlite::types::FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for BudgetPeriod {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(ValueRef::Text(
+            self.as_str().as_bytes(),
+        )))
+    }
+}
+
+/// What the payments counted against a budget are for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum BudgetTarget {
+    Category(String),
+    Expense(String),
+}
+
+impl Display for BudgetTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetTarget::Category(category) => write!(f, "{category}"),
+            BudgetTarget::Expense(expense) => write!(f, "{expense} (expense)"),
+        }
+    }
+}
+
+/// Most that should be spent on a category or expense per month or year.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub(crate) struct Budget {
+    pub(crate) id: i32,
+    pub(crate) target: BudgetTarget,
+    pub(crate) period: BudgetPeriod,
+    pub(crate) limit: Money,
+}
+
+/// How the payments of an expense are shared between members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SplitRule {
+    /// Everyone pays the same.
+    Equal,
+    /// Everyone pays a percentage of each payment.
+    Percentage,
+    /// Everyone pays a fixed amount, with any difference from the amount paid shared equally.
+    Fixed,
+}
+
+impl SplitRule {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SplitRule::Equal => "equal",
+            SplitRule::Percentage => "percentage",
+            SplitRule::Fixed => "fixed",
+        }
+    }
+}
+
+impl Display for SplitRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromSql for SplitRule {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("equal") => FromSqlResult::Ok(SplitRule::Equal),
+            Ok("percentage") => FromSqlResult::Ok(SplitRule::Percentage),
+            Ok("fixed") => FromSqlResult::Ok(SplitRule::Fixed),
+            _ => FromSqlResult::Err(rusqlite::types::FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for SplitRule {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(ValueRef::Text(
+            self.as_str().as_bytes(),
+        )))
+    }
+}
+
+/// Members sharing an expense, with their percentage or fixed amount. Equal splits have no shares.
+#[derive(Debug, Clone)]
+pub(crate) struct Split {
+    pub(crate) rule: SplitRule,
+    pub(crate) shares: Vec<(String, Option<Amount>)>,
+}
+
+/// Conversion rate from one currency to another, valid from `effective_date` onwards.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub(crate) struct ExchangeRate {
+    pub(crate) id: i32,
+    pub(crate) from_currency: Currency,
+    pub(crate) to_currency: Currency,
+    pub(crate) rate: Decimal,
+    pub(crate) effective_date: chrono::NaiveDate,
+}
+
+/// Days on which an expense is not due. Lasts until resumed if it has no end date.
+#[derive(Debug, Clone)]
+pub(crate) struct Pause {
+    pub(crate) id: i32,
+    pub(crate) start_date: chrono::NaiveDate,
+    pub(crate) end_date: Option<chrono::NaiveDate>,
+}
+
+impl Pause {
+    pub(crate) fn contains(&self, date: chrono::NaiveDate) -> bool {
+        self.start_date <= date && self.end_date.is_none_or(|end_date| date <= end_date)
+    }
+}
+
+/// Day on which bills due are paid on the next or previous business day instead.
+#[derive(Debug, Clone)]
+pub(crate) struct Holiday {
+    pub(crate) date: chrono::NaiveDate,
+    pub(crate) name: String,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub(crate) struct Expense {
     pub(crate) id: i32,
     pub(crate) created_at: chrono::DateTime<Utc>,
     pub(crate) name: String,
-    pub(crate) periodicity: Periodicity,
+    pub(crate) recurrence: Recurrence,
     pub(crate) due_date_reference: chrono::DateTime<Utc>,
+    pub(crate) month_end: MonthEndPolicy,
+    pub(crate) business_day: BusinessDayRule,
+    /// Number of due dates of installment plans. One-off expenses have a single one.
+    pub(crate) installments: Option<u32>,
+    /// Date after which the expense is no longer due.
+    pub(crate) until: Option<chrono::NaiveDate>,
+    pub(crate) amount: Option<Money>,
+    /// Set for expenses whose amount varies between bills. Their amount is then only a fallback.
+    pub(crate) estimate: Option<Estimate>,
+    pub(crate) category: Option<String>,
+    pub(crate) tags: Vec<String>,
+    /// Who the expense is paid to.
+    pub(crate) payee: Option<String>,
+    /// Account, contract or customer number the payee knows the expense by.
+    pub(crate) reference: Option<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) notes: Option<String>,
+    /// Money coming in, such as a salary or rent received, rather than a bill.
+    pub(crate) income: bool,
+    /// Account the expense is usually paid from, such as a checking account or a credit card.
+    pub(crate) account: Option<String>,
+    pub(crate) pauses: Vec<Pause>,
+    /// Archived expenses are hidden from `list` and `overdue`, but keep their payments.
+    pub(crate) archived_at: Option<chrono::DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone)]
 pub(crate) struct NewExpense<'a> {
     pub(crate) created_at: chrono::DateTime<Utc>,
     pub(crate) name: &'a str,
-    pub(crate) periodicity: Periodicity,
+    pub(crate) recurrence: Recurrence,
     pub(crate) due_date_reference: chrono::DateTime<Utc>,
+    pub(crate) month_end: MonthEndPolicy,
+    pub(crate) business_day: BusinessDayRule,
+    /// Number of due dates of installment plans. One-off expenses have a single one.
+    pub(crate) installments: Option<u32>,
+    /// Date after which the expense is no longer due.
+    pub(crate) until: Option<chrono::NaiveDate>,
+    pub(crate) amount: Option<Money>,
+    /// Set for expenses whose amount varies between bills. Their amount is then only a fallback.
+    pub(crate) estimate: Option<Estimate>,
+    pub(crate) category: Option<String>,
+    pub(crate) tags: Vec<String>,
+    /// Who the expense is paid to.
+    pub(crate) payee: Option<String>,
+    /// Account, contract or customer number the payee knows the expense by.
+    pub(crate) reference: Option<String>,
+    pub(crate) url: Option<String>,
+    pub(crate) notes: Option<String>,
+    /// Money coming in, such as a salary or rent received, rather than a bill.
+    pub(crate) income: bool,
+    /// Account the expense is usually paid from, such as a checking account or a credit card.
+    pub(crate) account: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -116,6 +405,16 @@ pub(crate) struct Payment {
     pub(crate) paid_at: chrono::DateTime<Utc>,
     pub(crate) expense_name: String,
     pub(crate) due_date_of_expense: chrono::DateTime<Utc>,
+    pub(crate) amount: Option<Money>,
+    /// How it was paid. E.g., 'card' or 'bank transfer'.
+    pub(crate) method: Option<String>,
+    /// Confirmation or transaction code given by the payee or the bank.
+    pub(crate) confirmation: Option<String>,
+    pub(crate) note: Option<String>,
+    /// Member who made the payment, for expenses split between members.
+    pub(crate) paid_by: Option<String>,
+    /// Account the payment was made from.
+    pub(crate) account: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -124,4 +423,14 @@ pub(crate) struct NewPayment<'a> {
     pub(crate) paid_at: chrono::DateTime<Utc>,
     pub(crate) expense_name: &'a str,
     pub(crate) due_date_of_expense: chrono::DateTime<Utc>,
+    pub(crate) amount: Option<Money>,
+    /// How it was paid. E.g., 'card' or 'bank transfer'.
+    pub(crate) method: Option<String>,
+    /// Confirmation or transaction code given by the payee or the bank.
+    pub(crate) confirmation: Option<String>,
+    pub(crate) note: Option<String>,
+    /// Member who made the payment, for expenses split between members.
+    pub(crate) paid_by: Option<String>,
+    /// Account the payment was made from.
+    pub(crate) account: Option<String>,
 }
@@ -0,0 +1,104 @@
+use clap::ValueEnum;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+/// How reporting commands print their rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    Table,
+    Json,
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+impl Format {
+    /// Whether the output is meant for people rather than scripts, and can thus be decorated.
+    pub(crate) fn is_human_readable(&self) -> bool {
+        matches!(self, Format::Table | Format::Markdown)
+    }
+}
+
+/// Rows printed by `print_rows`, with the columns machine-readable formats give them. These are
+/// the serialized fields, which can differ from the columns of the table.
+pub(crate) trait Columns {
+    const COLUMNS: &'static [&'static str];
+}
+
+pub(crate) fn print_rows<T: Tabled + Serialize + Columns>(
+    rows: &[T],
+    format: Format,
+) -> Result<()> {
+    match format {
+        Format::Table => println!("{}", Table::new(rows)),
+        Format::Markdown => println!("{}", Table::new(rows).with(Style::markdown())),
+        Format::Json => println!("{}", serde_json::to_string_pretty(rows)?),
+        Format::Csv => write_delimited(std::io::stdout(), rows, b',')?,
+        Format::Tsv => write_delimited(std::io::stdout(), rows, b'\t')?,
+    }
+
+    Ok(())
+}
+
+/// The header is written even without rows, so that scripts can tell an empty result apart.
+fn write_delimited<W: std::io::Write, T: Serialize + Columns>(
+    writer: W,
+    rows: &[T],
+    delimiter: u8,
+) -> Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+
+    if rows.is_empty() {
+        writer.write_record(T::COLUMNS)?;
+    }
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Checks that the columns of `T` are the ones its rows are serialized with.
+#[cfg(test)]
+pub(crate) fn assert_columns<T: Serialize + Columns>(rows: &[T]) {
+    let mut csv = Vec::new();
+    write_delimited(&mut csv, &rows[..1], b',').unwrap();
+
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().next().unwrap(), T::COLUMNS.join(","));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Row {
+        name: &'static str,
+        #[serde(skip)]
+        _hidden: bool,
+        total: u32,
+    }
+
+    impl Columns for Row {
+        const COLUMNS: &'static [&'static str] = &["name", "total"];
+    }
+
+    #[test]
+    fn test_delimited_header() {
+        let mut csv = Vec::new();
+        write_delimited::<_, Row>(&mut csv, &[], b'\t').unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "name\ttotal\n");
+
+        assert_columns(&[Row {
+            name: "Rent",
+            _hidden: false,
+            total: 1,
+        }]);
+    }
+}
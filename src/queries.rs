@@ -1,139 +1,860 @@
-use std::{fs::File, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    path::{Path, PathBuf},
+};
 
-use rusqlite::{Connection, Error, Result};
+use chrono::NaiveDate;
+use rusqlite::{Connection, Error, OptionalExtension, Result, params};
+use rust_decimal::Decimal;
 
-use crate::model::{Expense, NewExpense, NewPayment, Payment};
+use crate::calendar::Calendar;
+use crate::model::{
+    Amount, Budget, BudgetPeriod, BudgetTarget, Currency, ExchangeRate, Expense, Holiday, Money,
+    NewExpense, NewPayment, Pause, Payment, Split,
+};
 
-pub(crate) fn get_data_path() -> PathBuf {
-    let dir_path = std::env::home_dir()
-        .expect("should have home dir defined.")
-        .join(".expenses");
-    _ = std::fs::create_dir_all(&dir_path);
+const DEFAULT_LEDGER: &str = "data";
 
-    let file_path = dir_path.join("data.sqlite");
+/// Directory holding the ledgers when no explicit database path is given.
+///
+/// `~/.expenses` is kept if it already exists, so that older installs keep working. Otherwise
+/// `$XDG_DATA_HOME/expenses` is used, falling back to `~/.local/share/expenses` on Linux.
+pub(crate) fn get_data_dir() -> color_eyre::Result<PathBuf> {
+    let home_dir = std::env::home_dir();
+
+    if let Some(legacy_dir) = home_dir.as_ref().map(|home| home.join(".expenses"))
+        && legacy_dir.is_dir()
+    {
+        return Ok(legacy_dir);
+    }
+
+    if let Some(xdg_data_home) = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+        && xdg_data_home.is_absolute()
+    {
+        return Ok(xdg_data_home.join("expenses"));
+    }
+
+    let Some(home_dir) = home_dir else {
+        return Err(color_eyre::Report::msg(
+            "could not find a home directory. Please pass the database path with --db or EXPENSES_DB",
+        ));
+    };
+
+    if cfg!(target_os = "linux") {
+        Ok(home_dir.join(".local").join("share").join("expenses"))
+    } else {
+        Ok(home_dir.join(".expenses"))
+    }
+}
+
+/// Ledger names become file names, so they cannot point outside the data dir.
+fn validate_ledger_name(ledger: &str) -> color_eyre::Result<()> {
+    if ledger.is_empty()
+        || !ledger
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(color_eyre::Report::msg(format!(
+            "invalid ledger name: {}. Use only letters, digits, '-' and '_'",
+            ledger
+        )));
+    }
+
+    Ok(())
+}
+
+/// Resolves where the database lives, creating it if needed.
+///
+/// An explicit path wins. Otherwise the ledger (`data` by default) is looked up in the data dir.
+pub(crate) fn get_data_path(
+    db: Option<&Path>,
+    ledger: Option<&str>,
+) -> color_eyre::Result<PathBuf> {
+    let file_path = match db {
+        Some(db) => db.to_path_buf(),
+        None => {
+            let ledger = ledger.unwrap_or(DEFAULT_LEDGER);
+            validate_ledger_name(ledger)?;
+
+            get_data_dir()?.join(format!("{ledger}.sqlite"))
+        }
+    };
+
+    if let Some(dir_path) = file_path.parent()
+        && !dir_path.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(dir_path)?;
+    }
     _ = File::create_new(&file_path); // Err in case file exists, so just ignoring it.
 
-    file_path
+    Ok(file_path)
+}
+
+/// Names of the ledgers found in the data dir.
+pub(crate) fn get_ledgers() -> color_eyre::Result<Vec<String>> {
+    let Ok(entries) = std::fs::read_dir(get_data_dir()?) else {
+        return Ok(Vec::new());
+    };
+
+    let mut ledgers: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            (path.extension()? == "sqlite")
+                .then(|| path.file_stem()?.to_str().map(str::to_string))
+                .flatten()
+        })
+        .collect();
+    ledgers.sort();
+
+    Ok(ledgers)
 }
 
-pub(crate) fn create_tables(conn: &Connection) -> Result<()> {
+/// Id of the category with the given name, which is created if needed.
+fn get_or_create_category(conn: &Connection, name: &str) -> Result<i32> {
     conn.execute(
-        "CREATE TABLE expense (
-                 id                  INTEGER PRIMARY KEY,
-                 created_at          TEXT NOT NULL,
-                 name                TEXT UNIQUE NOT NULL,
-                 periodicity         TEXT NOT NULL,
-                 due_date_reference  TEXT NOT NULL
-              )",
-        (),
+        "INSERT INTO category (name) VALUES (?1) ON CONFLICT (name) DO NOTHING",
+        (name,),
     )?;
+
+    conn.query_row("SELECT id FROM category WHERE name = ?1", (name,), |row| {
+        row.get(0)
+    })
+}
+
+/// Replaces the tags of an expense, creating the ones that do not exist yet.
+fn set_tags(conn: &Connection, expense_id: i32, tags: &[String]) -> Result<()> {
     conn.execute(
-    "CREATE TABLE payment (
-                 id                  INTEGER PRIMARY KEY,
-                 created_at          TEXT NOT NULL,
-                 paid_at             TEXT NOT NULL,
-                 expense_name        TEXT NOT NULL,
-                 due_date_of_expense TEXT NOT NULL,
-                 FOREIGN KEY (expense_name) REFERENCES expense(name) ON DELETE CASCADE ON UPDATE CASCADE
-              )",
-    (),
-)?;
+        "DELETE FROM expense_tag WHERE expense_id = ?1",
+        (expense_id,),
+    )?;
+
+    for tag in tags {
+        conn.execute(
+            "INSERT INTO tag (name) VALUES (?1) ON CONFLICT (name) DO NOTHING",
+            (tag,),
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO expense_tag (expense_id, tag_id) SELECT ?1, id FROM tag WHERE name = ?2",
+            (expense_id, tag),
+        )?;
+    }
 
     Ok(())
 }
 
-pub(crate) fn add_expense(conn: &Connection, expense: &NewExpense) -> Result<()> {
+fn get_tags(conn: &Connection, expense_id: i32) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.name FROM tag t JOIN expense_tag et ON et.tag_id = t.id
+         WHERE et.expense_id = ?1 ORDER BY t.name",
+    )?;
+
+    stmt.query_map([expense_id], |row| row.get(0))?.collect()
+}
+
+/// Names of every income entry, archived ones included, so that their payments can be told apart.
+pub(crate) fn get_income_names(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM expense WHERE income")?;
+
+    stmt.query_map([], |row| row.get(0))?.collect()
+}
+
+/// Category of every expense that has one, archived ones included, by expense name.
+pub(crate) fn get_expense_categories(conn: &Connection) -> Result<HashMap<String, String>> {
+    let mut stmt = conn
+        .prepare("SELECT e.name, c.name FROM expense e JOIN category c ON c.id = e.category_id")?;
+
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+/// Should be run in a transaction, as the category and tags of the expense are stored apart.
+pub(crate) fn add_expense(conn: &Connection, expense: &NewExpense) -> Result<(), Error> {
+    let category_id = expense
+        .category
+        .as_deref()
+        .map(|category| get_or_create_category(conn, category))
+        .transpose()?;
+
     conn.execute(
-    "INSERT INTO expense (created_at, name, periodicity, due_date_reference) VALUES (?1, ?2, ?3, ?4)",
-    (
+    "INSERT INTO expense (created_at, name, recurrence, due_date_reference, amount, currency, month_end, business_day, installments, until, category_id, payee, reference, url, notes, estimate, income, account_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, (SELECT id FROM account WHERE name = ?18))",
+    params![
         &expense.created_at,
         &expense.name,
-        &expense.periodicity,
+        &expense.recurrence,
         &expense.due_date_reference,
-    ),
+        expense.amount.map(|m| m.amount),
+        expense.amount.map(|m| m.currency),
+        &expense.month_end,
+        &expense.business_day,
+        expense.installments,
+        expense.until,
+        category_id,
+        &expense.payee,
+        &expense.reference,
+        &expense.url,
+        &expense.notes,
+        expense.estimate,
+        expense.income,
+        &expense.account,
+    ],
 )?;
+    let expense_id = i32::try_from(conn.last_insert_rowid()).expect("ids fit in an i32");
+    set_tags(conn, expense_id, &expense.tags)?;
+
+    Ok(())
+}
+
+/// Updates the expense with the same id. Renames are carried over to its payments by the foreign key.
+///
+/// Should be run in a transaction, as the category and tags of the expense are stored apart.
+pub(crate) fn update_expense(conn: &Connection, expense: &Expense) -> Result<(), Error> {
+    let category_id = expense
+        .category
+        .as_deref()
+        .map(|category| get_or_create_category(conn, category))
+        .transpose()?;
+
+    conn.execute(
+        "UPDATE expense SET name = ?2, recurrence = ?3, due_date_reference = ?4, amount = ?5, currency = ?6, month_end = ?7, business_day = ?8, installments = ?9, until = ?10, category_id = ?11, payee = ?12, reference = ?13, url = ?14, notes = ?15, estimate = ?16, income = ?17, account_id = (SELECT id FROM account WHERE name = ?18) WHERE id = ?1",
+        params![
+            expense.id,
+            &expense.name,
+            &expense.recurrence,
+            &expense.due_date_reference,
+            expense.amount.map(|m| m.amount),
+            expense.amount.map(|m| m.currency),
+            &expense.month_end,
+            &expense.business_day,
+            expense.installments,
+            expense.until,
+            category_id,
+            &expense.payee,
+            &expense.reference,
+            &expense.url,
+            &expense.notes,
+            expense.estimate,
+            expense.income,
+            &expense.account,
+        ],
+    )?;
+    set_tags(conn, expense.id, &expense.tags)?;
 
     Ok(())
 }
 
 pub(crate) fn add_payment(conn: &Connection, payment: &NewPayment) -> Result<(), Error> {
     conn.execute(
-        "INSERT INTO payment (created_at, paid_at, expense_name, due_date_of_expense) VALUES (?1, ?2, ?3, ?4)",
+        "INSERT INTO payment (created_at, paid_at, expense_name, due_date_of_expense, amount, currency, method, confirmation, note, member_id, account_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, (SELECT id FROM member WHERE name = ?10), (SELECT id FROM account WHERE name = ?11))",
         (
             &payment.created_at,
             &payment.paid_at,
             &payment.expense_name,
             &payment.due_date_of_expense,
+            payment.amount.map(|m| m.amount),
+            payment.amount.map(|m| m.currency),
+            &payment.method,
+            &payment.confirmation,
+            &payment.note,
+            &payment.paid_by,
+            &payment.account,
+        ),
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn get_payment_by_id(conn: &Connection, id: i32) -> Result<Option<Payment>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.created_at, p.paid_at, p.expense_name, p.due_date_of_expense, p.amount, p.currency, p.method, p.confirmation, p.note, m.name, a.name
+         FROM payment p LEFT JOIN member m ON m.id = p.member_id
+         LEFT JOIN account a ON a.id = p.account_id WHERE p.id = ?1",
+    )?;
+
+    stmt.query_row([id], |row| {
+        Ok(Payment {
+            id: row.get(0)?,
+            created_at: row.get(1)?,
+            paid_at: row.get(2)?,
+            expense_name: row.get(3)?,
+            due_date_of_expense: row.get(4)?,
+            amount: Money::from_columns(row.get(5)?, row.get(6)?),
+            method: row.get(7)?,
+            confirmation: row.get(8)?,
+            note: row.get(9)?,
+            paid_by: row.get(10)?,
+            account: row.get(11)?,
+        })
+    })
+    .optional()
+}
+
+/// The payment covering the latest due date of the expense, which 'unpay' removes.
+pub(crate) fn get_latest_payment(conn: &Connection, expense_name: &str) -> Result<Option<Payment>> {
+    Ok(get_payments(conn, Some(expense_name))?
+        .into_iter()
+        .max_by_key(|p| (p.due_date_of_expense, p.id)))
+}
+
+/// Fails if the payment does not exist, or if another payment of the expense already covers its
+/// due date.
+pub(crate) fn update_payment(conn: &Connection, payment: &Payment) -> color_eyre::Result<()> {
+    let is_taken: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM payment WHERE expense_name = ?1 AND due_date_of_expense = ?2 AND id <> ?3)",
+        (&payment.expense_name, &payment.due_date_of_expense, payment.id),
+        |row| row.get(0),
+    )?;
+    if is_taken {
+        return Err(color_eyre::Report::msg(format!(
+            "due date {} of expense {} is already paid",
+            payment
+                .due_date_of_expense
+                .with_timezone(&chrono::Local)
+                .date_naive(),
+            payment.expense_name
+        )));
+    }
+
+    let updated = conn.execute(
+        "UPDATE payment SET paid_at = ?2, due_date_of_expense = ?3, amount = ?4, currency = ?5, method = ?6, confirmation = ?7, note = ?8, member_id = (SELECT id FROM member WHERE name = ?9), account_id = (SELECT id FROM account WHERE name = ?10) WHERE id = ?1",
+        (
+            payment.id,
+            &payment.paid_at,
+            &payment.due_date_of_expense,
+            payment.amount.map(|m| m.amount),
+            payment.amount.map(|m| m.currency),
+            &payment.method,
+            &payment.confirmation,
+            &payment.note,
+            &payment.paid_by,
+            &payment.account,
         ),
     )?;
+    if updated == 0 {
+        return Err(color_eyre::Report::msg(format!(
+            "payment with id {} does not exist",
+            payment.id
+        )));
+    }
 
     Ok(())
 }
 
+/// Returns whether a payment with the given id existed.
+pub(crate) fn delete_payment(conn: &Connection, id: i32) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM payment WHERE payment.id = ?1", (id,))?;
+
+    Ok(deleted > 0)
+}
+
 pub(crate) fn delete_expense(conn: &Connection, name: &str) -> Result<()> {
     conn.execute("DELETE FROM expense WHERE expense.name = ?1", (name,))?;
 
     Ok(())
 }
 
+pub(crate) fn archive_expense(
+    conn: &Connection,
+    id: i32,
+    archived_at: Option<chrono::DateTime<chrono::Utc>>,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE expense SET archived_at = ?2 WHERE id = ?1",
+        (id, archived_at),
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn add_pause(
+    conn: &Connection,
+    expense_id: i32,
+    start_date: NaiveDate,
+    end_date: Option<NaiveDate>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO pause (expense_id, start_date, end_date) VALUES (?1, ?2, ?3)",
+        (expense_id, start_date, end_date),
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn update_pause(conn: &Connection, pause: &Pause) -> Result<()> {
+    conn.execute(
+        "UPDATE pause SET start_date = ?2, end_date = ?3 WHERE id = ?1",
+        (pause.id, pause.start_date, pause.end_date),
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn delete_pause(conn: &Connection, id: i32) -> Result<()> {
+    conn.execute("DELETE FROM pause WHERE id = ?1", (id,))?;
+
+    Ok(())
+}
+
+fn get_pauses(conn: &Connection, expense_id: i32) -> Result<Vec<Pause>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, start_date, end_date FROM pause WHERE expense_id = ?1 ORDER BY start_date",
+    )?;
+
+    stmt.query_map([expense_id], |row| {
+        Ok(Pause {
+            id: row.get(0)?,
+            start_date: row.get(1)?,
+            end_date: row.get(2)?,
+        })
+    })?
+    .collect()
+}
+
 pub(crate) fn get_expense_by_name(conn: &Connection, name: &str) -> Result<Option<Expense>> {
-    let mut stmt = conn.prepare("SELECT id, created_at, due_date_reference, name, periodicity FROM expense WHERE expense.name = ?1")?;
+    let mut stmt = conn.prepare("SELECT e.id, e.created_at, e.due_date_reference, e.name, e.recurrence, e.amount, e.currency, e.month_end, e.business_day, e.installments, e.until, e.archived_at, c.name, e.payee, e.reference, e.url, e.notes, e.estimate, e.income, a.name
+         FROM expense e LEFT JOIN category c ON c.id = e.category_id
+         LEFT JOIN account a ON a.id = e.account_id WHERE e.name = ?1")?;
+
+    let expense = stmt
+        .query_map([name], |row| {
+            Ok(Expense {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                due_date_reference: row.get(2)?,
+                name: row.get(3)?,
+                recurrence: row.get(4)?,
+                month_end: row.get(7)?,
+                business_day: row.get(8)?,
+                installments: row.get(9)?,
+                until: row.get(10)?,
+                amount: Money::from_columns(row.get(5)?, row.get(6)?),
+                estimate: row.get(17)?,
+                category: row.get(12)?,
+                tags: Vec::new(),
+                payee: row.get(13)?,
+                reference: row.get(14)?,
+                url: row.get(15)?,
+                notes: row.get(16)?,
+                income: row.get(18)?,
+                account: row.get(19)?,
+                pauses: Vec::new(),
+                archived_at: row.get(11)?,
+            })
+        })
+        .map(|x| x.flatten().next())?;
+
+    expense
+        .map(|mut expense| {
+            expense.tags = get_tags(conn, expense.id)?;
+            expense.pauses = get_pauses(conn, expense.id)?;
+            Ok(expense)
+        })
+        .transpose()
+}
+
+pub(crate) fn set_exchange_rate(
+    conn: &Connection,
+    from: Currency,
+    to: Currency,
+    rate: Amount,
+    effective_date: NaiveDate,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO exchange_rate (from_currency, to_currency, rate, effective_date) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (from_currency, to_currency, effective_date) DO UPDATE SET rate = excluded.rate",
+        (from, to, rate, effective_date),
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn get_exchange_rates(conn: &Connection) -> Result<Vec<ExchangeRate>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, from_currency, to_currency, rate, effective_date FROM exchange_rate
+         ORDER BY from_currency, to_currency, effective_date DESC",
+    )?;
+
+    stmt.query_map([], |row| {
+        let rate: Amount = row.get(3)?;
+
+        Ok(ExchangeRate {
+            id: row.get(0)?,
+            from_currency: row.get(1)?,
+            to_currency: row.get(2)?,
+            rate: rate.0,
+            effective_date: row.get(4)?,
+        })
+    })?
+    .collect()
+}
+
+/// Finds the most recent rate on or before `date` to convert `from` into `to`.
+///
+/// If only the opposite direction was registered, its inverse is used.
+pub(crate) fn get_exchange_rate(
+    conn: &Connection,
+    from: Currency,
+    to: Currency,
+    date: NaiveDate,
+) -> Result<Option<Decimal>> {
+    if from == to {
+        return Ok(Some(Decimal::ONE));
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT from_currency, rate FROM exchange_rate
+         WHERE ((from_currency = ?1 AND to_currency = ?2) OR (from_currency = ?2 AND to_currency = ?1))
+           AND effective_date <= ?3
+         ORDER BY effective_date DESC, from_currency = ?1 DESC
+         LIMIT 1",
+    )?;
+
+    let found = stmt
+        .query_map((from, to, date), |row| {
+            let from_currency: Currency = row.get(0)?;
+            let rate: Amount = row.get(1)?;
+
+            Ok((from_currency, rate.0))
+        })?
+        .next()
+        .transpose()?;
+
+    Ok(found.and_then(|(from_currency, rate)| {
+        if from_currency == from {
+            Some(rate)
+        } else {
+            Decimal::ONE.checked_div(rate)
+        }
+    }))
+}
+
+/// Adds a holiday, renaming it if the date was already registered.
+pub(crate) fn set_holiday(conn: &Connection, holiday: &Holiday) -> Result<()> {
+    conn.execute(
+        "INSERT INTO holiday (date, name) VALUES (?1, ?2)
+         ON CONFLICT (date) DO UPDATE SET name = excluded.name",
+        (holiday.date, &holiday.name),
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn delete_holiday(conn: &Connection, date: NaiveDate) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM holiday WHERE date = ?1", (date,))?;
+
+    Ok(deleted > 0)
+}
+
+pub(crate) fn get_holidays(conn: &Connection) -> Result<Vec<Holiday>> {
+    let mut stmt = conn.prepare("SELECT date, name FROM holiday ORDER BY date")?;
+
+    stmt.query_map([], |row| {
+        Ok(Holiday {
+            date: row.get(0)?,
+            name: row.get(1)?,
+        })
+    })?
+    .collect()
+}
+
+/// Replaces the limit of the budget for the same target and period, if there is one. Categories
+/// are created if needed. Returns false if the expense does not exist.
+pub(crate) fn set_budget(
+    conn: &Connection,
+    target: &BudgetTarget,
+    period: BudgetPeriod,
+    limit: Money,
+) -> Result<bool> {
+    let (category_id, expense_id) = match target {
+        BudgetTarget::Category(category) => (Some(get_or_create_category(conn, category)?), None),
+        BudgetTarget::Expense(expense) => {
+            let expense_id: Option<i32> = conn
+                .query_row(
+                    "SELECT id FROM expense WHERE name = ?1",
+                    (expense,),
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(expense_id) = expense_id else {
+                return Ok(false);
+            };
+            (None, Some(expense_id))
+        }
+    };
+
+    conn.execute(
+        "INSERT OR REPLACE INTO budget (category_id, expense_id, period, amount, currency) VALUES (?1, ?2, ?3, ?4, ?5)",
+        (category_id, expense_id, period, limit.amount, limit.currency),
+    )?;
+
+    Ok(true)
+}
+
+pub(crate) fn delete_budget(
+    conn: &Connection,
+    target: &BudgetTarget,
+    period: BudgetPeriod,
+) -> Result<bool> {
+    let deleted = match target {
+        BudgetTarget::Category(category) => conn.execute(
+            "DELETE FROM budget WHERE period = ?2
+               AND category_id = (SELECT id FROM category WHERE name = ?1)",
+            (category, period),
+        )?,
+        BudgetTarget::Expense(expense) => conn.execute(
+            "DELETE FROM budget WHERE period = ?2
+               AND expense_id = (SELECT id FROM expense WHERE name = ?1)",
+            (expense, period),
+        )?,
+    };
+
+    Ok(deleted > 0)
+}
+
+pub(crate) fn get_budgets(conn: &Connection) -> Result<Vec<Budget>> {
+    let mut stmt = conn.prepare(
+        "SELECT b.id, c.name, e.name, b.period, b.amount, b.currency FROM budget b
+         LEFT JOIN category c ON c.id = b.category_id
+         LEFT JOIN expense e ON e.id = b.expense_id
+         ORDER BY b.expense_id IS NOT NULL, COALESCE(c.name, e.name) COLLATE NOCASE, b.period",
+    )?;
+
+    stmt.query_map([], |row| {
+        let category: Option<String> = row.get(1)?;
+        let target = match category {
+            Some(category) => BudgetTarget::Category(category),
+            None => BudgetTarget::Expense(row.get(2)?),
+        };
+
+        Ok(Budget {
+            id: row.get(0)?,
+            target,
+            period: row.get(3)?,
+            limit: Money {
+                amount: row.get(4)?,
+                currency: row.get(5)?,
+            },
+        })
+    })?
+    .collect()
+}
+
+pub(crate) fn add_member(conn: &Connection, name: &str) -> Result<(), Error> {
+    conn.execute("INSERT INTO member (name) VALUES (?1)", (name,))?;
+
+    Ok(())
+}
+
+/// Returns whether the member existed. Fails if the member made payments or shares an expense.
+pub(crate) fn delete_member(conn: &Connection, name: &str) -> Result<bool, Error> {
+    let deleted = conn.execute("DELETE FROM member WHERE name = ?1", (name,))?;
+
+    Ok(deleted > 0)
+}
+
+pub(crate) fn get_members(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM member ORDER BY name COLLATE NOCASE")?;
+
+    stmt.query_map([], |row| row.get(0))?.collect()
+}
+
+pub(crate) fn add_account(conn: &Connection, name: &str) -> Result<(), Error> {
+    conn.execute("INSERT INTO account (name) VALUES (?1)", (name,))?;
+
+    Ok(())
+}
+
+/// Returns whether the account existed. Fails if expenses or payments still use it.
+pub(crate) fn delete_account(conn: &Connection, name: &str) -> Result<bool, Error> {
+    let deleted = conn.execute("DELETE FROM account WHERE name = ?1", (name,))?;
+
+    Ok(deleted > 0)
+}
+
+pub(crate) fn get_accounts(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM account ORDER BY name COLLATE NOCASE")?;
+
+    stmt.query_map([], |row| row.get(0))?.collect()
+}
+
+/// Replaces how the expense is split. Should be run in a transaction.
+pub(crate) fn set_split(conn: &Connection, expense_id: i32, split: &Split) -> Result<()> {
+    delete_split(conn, expense_id)?;
+    conn.execute(
+        "INSERT INTO split (expense_id, rule) VALUES (?1, ?2)",
+        (expense_id, split.rule),
+    )?;
+
+    for (member, share) in &split.shares {
+        conn.execute(
+            "INSERT INTO split_share (expense_id, member_id, share)
+             VALUES (?1, (SELECT id FROM member WHERE name = ?2), ?3)",
+            (expense_id, member, share),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether the expense was split.
+pub(crate) fn delete_split(conn: &Connection, expense_id: i32) -> Result<bool> {
+    let deleted = conn.execute("DELETE FROM split WHERE expense_id = ?1", (expense_id,))?;
+
+    Ok(deleted > 0)
+}
+
+/// How every split expense, archived ones included, is split, by expense name.
+pub(crate) fn get_splits(conn: &Connection) -> Result<HashMap<String, Split>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.name, s.rule, m.name, ss.share FROM split s
+         JOIN expense e ON e.id = s.expense_id
+         JOIN split_share ss ON ss.expense_id = s.expense_id
+         JOIN member m ON m.id = ss.member_id
+         ORDER BY e.name, m.name COLLATE NOCASE",
+    )?;
+
+    let mut splits: HashMap<String, Split> = HashMap::new();
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+    })?;
+    for row in rows {
+        let (expense, rule, member, share): (String, _, String, Option<Amount>) = row?;
+        splits
+            .entry(expense)
+            .or_insert(Split {
+                rule,
+                shares: Vec::new(),
+            })
+            .shares
+            .push((member, share));
+    }
+
+    Ok(splits)
+}
 
-    stmt.query_map([name], |row| {
-        Ok(Expense {
+pub(crate) fn get_calendar(conn: &Connection) -> Result<Calendar> {
+    Ok(get_holidays(conn)?
+        .into_iter()
+        .map(|holiday| holiday.date)
+        .collect())
+}
+
+/// Every payment ever registered, optionally restricted to a single expense, oldest first.
+pub(crate) fn get_payments(conn: &Connection, expense_name: Option<&str>) -> Result<Vec<Payment>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.created_at, p.paid_at, p.expense_name, p.due_date_of_expense, p.amount, p.currency, p.method, p.confirmation, p.note, m.name, a.name
+         FROM payment p LEFT JOIN member m ON m.id = p.member_id
+         LEFT JOIN account a ON a.id = p.account_id
+         WHERE ?1 IS NULL OR p.expense_name = ?1
+         ORDER BY p.paid_at",
+    )?;
+
+    stmt.query_map([expense_name], |row| {
+        Ok(Payment {
             id: row.get(0)?,
             created_at: row.get(1)?,
-            due_date_reference: row.get(2)?,
-            name: row.get(3)?,
-            periodicity: row.get(4)?,
+            paid_at: row.get(2)?,
+            expense_name: row.get(3)?,
+            due_date_of_expense: row.get(4)?,
+            amount: Money::from_columns(row.get(5)?, row.get(6)?),
+            method: row.get(7)?,
+            confirmation: row.get(8)?,
+            note: row.get(9)?,
+            paid_by: row.get(10)?,
+            account: row.get(11)?,
         })
-    })
-    .map(|x| x.flatten().next())
+    })?
+    .collect()
 }
 
+/// Every expense not archived, with its latest payment.
 pub(crate) fn get_entries(conn: &Connection) -> Result<Vec<(Expense, Option<Payment>)>> {
     let mut stmt = conn.prepare(
         "SELECT 
   e.id AS expense_id,
   e.created_at AS expense_created_at,
   e.name AS expense_name,
-  e.periodicity,
+  e.recurrence,
   e.due_date_reference,
+  e.amount AS expense_amount,
+  e.currency AS expense_currency,
   p.id AS payment_id,
   p.created_at AS payment_created_at,
   p.paid_at,
   p.expense_name AS payment_expense_name,
-  p.due_date_of_expense AS payment_due_date_of_expense
+  p.due_date_of_expense AS payment_due_date_of_expense,
+  p.amount AS payment_amount,
+  p.currency AS payment_currency,
+  e.month_end,
+  e.business_day,
+  e.installments,
+  e.until,
+  e.archived_at,
+  c.name AS category,
+  e.payee,
+  e.reference,
+  e.url,
+  e.notes,
+  p.method,
+  p.confirmation,
+  p.note,
+  e.estimate,
+  e.income,
+  pm.name AS paid_by,
+  ea.name AS account,
+  pa.name AS payment_account
 FROM expense e
+LEFT JOIN category c ON c.id = e.category_id
+LEFT JOIN account ea ON ea.id = e.account_id
 LEFT JOIN (
-  SELECT p1.*
-  FROM payment p1
-  INNER JOIN (
-    SELECT expense_name, MAX(paid_at) AS latest_paid_at
-    FROM payment
-    GROUP BY expense_name
-  ) p2 ON p1.expense_name = p2.expense_name
-        AND p1.paid_at = p2.latest_paid_at
-) p ON e.name = p.expense_name;",
+  SELECT *, ROW_NUMBER() OVER (PARTITION BY expense_name ORDER BY paid_at DESC, id DESC) AS payment_rank
+  FROM payment
+) p ON e.name = p.expense_name AND p.payment_rank = 1
+LEFT JOIN member pm ON pm.id = p.member_id
+LEFT JOIN account pa ON pa.id = p.account_id
+WHERE e.archived_at IS NULL;",
     )?;
     let expenses = stmt.query_map([], |row| {
         let expense = Expense {
             id: row.get(0)?,
             created_at: row.get(1)?,
             name: row.get(2)?,
-            periodicity: row.get(3)?,
+            recurrence: row.get(3)?,
             due_date_reference: row.get(4)?,
+            month_end: row.get(14)?,
+            business_day: row.get(15)?,
+            installments: row.get(16)?,
+            until: row.get(17)?,
+            amount: Money::from_columns(row.get(5)?, row.get(6)?),
+            estimate: row.get(27)?,
+            category: row.get(19)?,
+            tags: Vec::new(),
+            payee: row.get(20)?,
+            reference: row.get(21)?,
+            url: row.get(22)?,
+            notes: row.get(23)?,
+            income: row.get(28)?,
+            account: row.get(30)?,
+            pauses: Vec::new(),
+            archived_at: row.get(18)?,
         };
-        let payment_id: Option<i32> = row.get(5)?;
+        let payment_id: Option<i32> = row.get(7)?;
         if payment_id.is_some() {
             Ok((
                 expense,
                 Some(Payment {
-                    id: row.get(5)?,
-                    created_at: row.get(6)?,
-                    paid_at: row.get(7)?,
-                    expense_name: row.get(8)?,
-                    due_date_of_expense: row.get(9)?,
+                    id: row.get(7)?,
+                    created_at: row.get(8)?,
+                    paid_at: row.get(9)?,
+                    expense_name: row.get(10)?,
+                    due_date_of_expense: row.get(11)?,
+                    amount: Money::from_columns(row.get(12)?, row.get(13)?),
+                    method: row.get(24)?,
+                    confirmation: row.get(25)?,
+                    note: row.get(26)?,
+                    paid_by: row.get(29)?,
+                    account: row.get(31)?,
                 }),
             ))
         } else {
@@ -144,10 +865,195 @@ LEFT JOIN (
     let mut expenses_to_return = Vec::new();
 
     for expense in expenses {
-        let expense = expense?;
+        let (mut expense, payment) = expense?;
+        expense.tags = get_tags(conn, expense.id)?;
+        expense.pauses = get_pauses(conn, expense.id)?;
 
-        expenses_to_return.push(expense);
+        expenses_to_return.push((expense, payment));
     }
 
     Ok(expenses_to_return)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_path() {
+        assert!(validate_ledger_name("").is_err());
+        assert!(validate_ledger_name("../x").is_err());
+        assert!(validate_ledger_name("work").is_ok());
+
+        // An explicit path wins over the ledger, which is then not even checked.
+        let db = std::env::temp_dir().join(format!("expenses-test-{}.sqlite", std::process::id()));
+        assert_eq!(get_data_path(Some(&db), Some("../x")).unwrap(), db);
+        assert!(db.is_file());
+        std::fs::remove_file(db).unwrap();
+    }
+
+    #[test]
+    fn test_exchange_rate_lookup() {
+        let conn = crate::migrations::open_in_memory();
+
+        let eur: Currency = "EUR".parse().unwrap();
+        let brl: Currency = "BRL".parse().unwrap();
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+
+        set_exchange_rate(&conn, eur, brl, "6".parse().unwrap(), date("2025-01-01")).unwrap();
+        set_exchange_rate(
+            &conn,
+            brl,
+            eur,
+            "0.125".parse().unwrap(),
+            date("2025-06-01"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_exchange_rate(&conn, eur, brl, date("2024-12-31")).unwrap(),
+            None
+        );
+        assert_eq!(
+            get_exchange_rate(&conn, eur, brl, date("2025-03-01")).unwrap(),
+            Some(Decimal::from(6))
+        );
+        assert_eq!(
+            get_exchange_rate(&conn, eur, brl, date("2025-06-01")).unwrap(),
+            Some(Decimal::from(8))
+        );
+    }
+
+    #[test]
+    fn test_categories_and_tags() {
+        let conn = crate::migrations::open_in_memory();
+        let now = chrono::Utc::now();
+
+        add_expense(
+            &conn,
+            &NewExpense {
+                created_at: now,
+                name: "Water",
+                recurrence: "monthly".parse().unwrap(),
+                due_date_reference: now,
+                month_end: Default::default(),
+                business_day: Default::default(),
+                installments: None,
+                until: None,
+                amount: None,
+                estimate: None,
+                category: Some("Utilities".to_string()),
+                tags: vec!["shared".to_string(), "home".to_string()],
+                payee: None,
+                reference: None,
+                url: None,
+                notes: None,
+                income: false,
+                account: None,
+            },
+        )
+        .unwrap();
+
+        let mut expense = get_expense_by_name(&conn, "Water").unwrap().unwrap();
+        assert_eq!(expense.category.as_deref(), Some("Utilities"));
+        assert_eq!(expense.tags, ["home", "shared"]);
+
+        // Categories and tags are matched regardless of case.
+        expense.category = Some("UTILITIES".to_string());
+        expense.tags = vec!["Shared".to_string()];
+        update_expense(&conn, &expense).unwrap();
+
+        let (expense, _) = get_entries(&conn).unwrap().remove(0);
+        assert_eq!(expense.category.as_deref(), Some("Utilities"));
+        assert_eq!(expense.tags, ["shared"]);
+    }
+
+    #[test]
+    fn test_payment_corrections() {
+        let conn = crate::migrations::open_in_memory();
+        let date = |s| chrono::DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        let (january, february, march) = (
+            date("2026-01-05T03:00:01+00:00"),
+            date("2026-02-05T03:00:01+00:00"),
+            date("2026-03-05T03:00:01+00:00"),
+        );
+
+        add_expense(
+            &conn,
+            &NewExpense {
+                created_at: january,
+                name: "Rent",
+                recurrence: "monthly".parse().unwrap(),
+                due_date_reference: january,
+                month_end: Default::default(),
+                business_day: Default::default(),
+                installments: None,
+                until: None,
+                amount: None,
+                estimate: None,
+                category: None,
+                tags: Vec::new(),
+                payee: None,
+                reference: None,
+                url: None,
+                notes: None,
+                income: false,
+                account: None,
+            },
+        )
+        .unwrap();
+        // March was paid in advance, before the two others.
+        for (paid_at, due_date) in [
+            ("2026-01-01T12:00:00+00:00", march),
+            ("2026-01-02T12:00:00+00:00", january),
+            ("2026-02-03T12:00:00+00:00", february),
+        ] {
+            add_payment(
+                &conn,
+                &NewPayment {
+                    created_at: date(paid_at),
+                    paid_at: date(paid_at),
+                    expense_name: "Rent",
+                    due_date_of_expense: due_date,
+                    amount: None,
+                    method: None,
+                    confirmation: None,
+                    note: None,
+                    paid_by: None,
+                    account: None,
+                },
+            )
+            .unwrap();
+        }
+
+        // Unpaying removes the payment of the latest due date, even if it was not the last made.
+        let latest = get_latest_payment(&conn, "Rent").unwrap().unwrap();
+        assert_eq!(latest.due_date_of_expense, march);
+        assert!(delete_payment(&conn, latest.id).unwrap());
+        let due_dates: Vec<_> = get_payments(&conn, Some("Rent"))
+            .unwrap()
+            .iter()
+            .map(|p| p.due_date_of_expense)
+            .collect();
+        assert_eq!(due_dates, [january, february]);
+
+        // A payment cannot be moved onto a due date already paid.
+        let mut payment = get_payment_by_id(&conn, 2).unwrap().unwrap();
+        payment.due_date_of_expense = february;
+        assert!(update_payment(&conn, &payment).is_err());
+        payment.due_date_of_expense = march;
+        update_payment(&conn, &payment).unwrap();
+        assert_eq!(
+            get_payment_by_id(&conn, 2)
+                .unwrap()
+                .unwrap()
+                .due_date_of_expense,
+            march
+        );
+
+        assert!(get_payment_by_id(&conn, 42).unwrap().is_none());
+        assert!(!delete_payment(&conn, 42).unwrap());
+        payment.id = 42;
+        assert!(update_payment(&conn, &payment).is_err());
+    }
+}
@@ -0,0 +1,820 @@
+use chrono::{
+    DateTime, Datelike, Days, Months, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Weekday,
+};
+use clap::ValueEnum;
+use rusqlite::{
+    ToSql,
+    types::{FromSql, FromSqlResult, ValueRef},
+};
+use serde::{Serialize, Serializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+const OUT_OF_BOUNDS: &str =
+    "should not be reaching out of bounds for time operations. You might be in the FAR future? o_o";
+
+/// Longest interval accepted between due dates. Schedules further apart than that are surely a
+/// typo, and would take too long to step through.
+const MAX_INTERVAL: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// When an expense is due, relative to its reference date.
+///
+/// Stored as a subset of RFC 5545 RRULEs, such as `FREQ=MONTHLY;INTERVAL=2` or `FREQ=MONTHLY;BYDAY=2TU`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Recurrence {
+    /// Every `interval` days, weeks, months or years counting from the reference date.
+    Every { interval: u32, frequency: Frequency },
+    /// On each of `weekdays`, every `interval` weeks counting from the week of the reference date.
+    Weekdays {
+        interval: u32,
+        weekdays: Vec<Weekday>,
+    },
+    /// On the `nth` `weekday` of the month (counting from the end if negative), every `interval` months.
+    NthWeekday {
+        interval: u32,
+        nth: i8,
+        weekday: Weekday,
+    },
+}
+
+/// What to do with due dates whose day does not exist in a given month, such as the 31st in April.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub(crate) enum MonthEndPolicy {
+    /// Use the last day of that month instead.
+    #[default]
+    Clamp,
+    /// Use the first day of the following month instead.
+    Roll,
+    /// Always use the last day of the month, whatever the day of the reference date.
+    LastDay,
+}
+
+impl MonthEndPolicy {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MonthEndPolicy::Clamp => "clamp",
+            MonthEndPolicy::Roll => "roll",
+            MonthEndPolicy::LastDay => "last-day",
+        }
+    }
+}
+
+impl Display for MonthEndPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromSql for MonthEndPolicy {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str() {
+            Ok("clamp") => FromSqlResult::Ok(MonthEndPolicy::Clamp),
+            Ok("roll") => FromSqlResult::Ok(MonthEndPolicy::Roll),
+            Ok("last-day") => FromSqlResult::Ok(MonthEndPolicy::LastDay),
+            _ => FromSqlResult::Err(rusqlite::types::FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for MonthEndPolicy {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::Borrowed(ValueRef::Text(
+            self.as_str().as_bytes(),
+        )))
+    }
+}
+
+/// Resolves a wall-clock time, moving forward out of DST gaps.
+pub(crate) fn from_local<Tz: TimeZone>(tz: &Tz, naive: NaiveDateTime) -> DateTime<Tz> {
+    (0..=24)
+        .find_map(|hours| {
+            tz.from_local_datetime(&(naive + TimeDelta::hours(hours)))
+                .earliest()
+        })
+        .expect("a valid local time exists within a day")
+}
+
+fn months_between(from: NaiveDate, to: NaiveDate) -> i64 {
+    (to.year() as i64 - from.year() as i64) * 12 + to.month() as i64 - from.month() as i64
+}
+
+fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1)
+        .and_then(|first| first.checked_add_months(Months::new(1)))
+        .and_then(|first_of_next| first_of_next.pred_opt())
+        .expect(OUT_OF_BOUNDS)
+}
+
+/// The `months`-th month after `anchor`, on the same day if possible or as told by `month_end`.
+///
+/// Always counting from the anchor, rather than from the previous due date, avoids drifting
+/// after a clamped month (E.g., 31st, 30th, 28th, 28th...).
+fn add_months(anchor: NaiveDate, months: u32, month_end: MonthEndPolicy) -> Option<NaiveDate> {
+    let month = anchor
+        .with_day(1)?
+        .checked_add_months(Months::new(months))?;
+    let last_day = last_day_of_month(month);
+
+    match month_end {
+        MonthEndPolicy::LastDay => Some(last_day),
+        _ if anchor.day() <= last_day.day() => month.with_day(anchor.day()),
+        MonthEndPolicy::Clamp => Some(last_day),
+        MonthEndPolicy::Roll => last_day.succ_opt(),
+    }
+}
+
+fn nth_weekday_of_month(year: i32, month: u32, nth: i8, weekday: Weekday) -> Option<NaiveDate> {
+    if nth > 0 {
+        return NaiveDate::from_weekday_of_month_opt(year, month, weekday, nth as u8);
+    }
+
+    let first_of_next =
+        NaiveDate::from_ymd_opt(year, month, 1)?.checked_add_months(Months::new(1))?;
+    let last = first_of_next.pred_opt()?;
+    let offset = (last.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    let date = last - Days::new(offset as u64 + 7 * (-nth - 1) as u64);
+
+    (date.month() == month).then_some(date)
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday_code(code: &str) -> Option<Weekday> {
+    match code {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn ordinal(nth: i8) -> String {
+    match nth {
+        -1 => "last".to_string(),
+        n if n < 0 => format!("{} to last", ordinal(-n)),
+        1 => "1st".to_string(),
+        2 => "2nd".to_string(),
+        3 => "3rd".to_string(),
+        n => format!("{n}th"),
+    }
+}
+
+impl Recurrence {
+    /// Rough length of a period, used to decide how soon a due date is worth highlighting.
+    fn approximate_days(&self) -> u32 {
+        match self {
+            Recurrence::Every {
+                interval,
+                frequency,
+            } => {
+                let days = match frequency {
+                    Frequency::Daily => 1,
+                    Frequency::Weekly => 7,
+                    Frequency::Monthly => 30,
+                    Frequency::Yearly => 365,
+                };
+                interval.saturating_mul(days)
+            }
+            Recurrence::Weekdays { interval, weekdays } => {
+                interval.saturating_mul(7) / weekdays.len().max(1) as u32
+            }
+            Recurrence::NthWeekday { interval, .. } => interval.saturating_mul(30),
+        }
+    }
+
+    pub(crate) fn get_row_color_on_time_left(&self, days_left: i64) -> tabled::settings::Color {
+        let (red_range, yellow_range) = match self.approximate_days() {
+            0..=7 => (0..2, 2..=3),
+            8..=31 => (0..5, 5..=10),
+            32..=62 => (0..10, 10..=15),
+            63..=93 => (0..15, 15..=30),
+            94..=124 => (0..20, 20..=30),
+            _ => (0..30, 30..=60),
+        };
+
+        if red_range.contains(&days_left) {
+            return tabled::settings::Color::FG_RED;
+        } else if yellow_range.contains(&days_left) {
+            return tabled::settings::Color::FG_YELLOW;
+        }
+
+        tabled::settings::Color::empty()
+    }
+
+    /// The first due date at or after `instant`. Due dates are computed on the wall clock of
+    /// `instant`'s timezone, keeping the time of day of `reference`.
+    pub(crate) fn next_on_or_after<Tz: TimeZone>(
+        &self,
+        reference: &DateTime<Tz>,
+        instant: &DateTime<Tz>,
+        month_end: MonthEndPolicy,
+    ) -> DateTime<Tz> {
+        let tz = instant.timezone();
+        let reference = reference.with_timezone(&tz);
+        let start = if *instant > reference {
+            instant.naive_local()
+        } else {
+            reference.naive_local()
+        };
+        let anchor = reference.naive_local();
+
+        let next = match self {
+            Recurrence::Every {
+                interval,
+                frequency,
+            } => {
+                let nth = |k: u32| -> NaiveDateTime {
+                    let periods = k.checked_mul(*interval);
+                    match frequency {
+                        Frequency::Daily => {
+                            periods.and_then(|days| anchor.checked_add_days(Days::new(days as u64)))
+                        }
+                        Frequency::Weekly => periods
+                            .and_then(|weeks| anchor.checked_add_days(Days::new(7 * weeks as u64))),
+                        Frequency::Monthly => periods
+                            .and_then(|months| add_months(anchor.date(), months, month_end))
+                            .map(|date| date.and_time(anchor.time())),
+                        Frequency::Yearly => periods
+                            .and_then(|years| years.checked_mul(12))
+                            .and_then(|months| add_months(anchor.date(), months, month_end))
+                            .map(|date| date.and_time(anchor.time())),
+                    }
+                    .expect(OUT_OF_BOUNDS)
+                };
+
+                // Jumping close to `start` first keeps this O(1) on (start - reference).
+                let elapsed = match frequency {
+                    Frequency::Daily => (start.date() - anchor.date()).num_days(),
+                    Frequency::Weekly => (start.date() - anchor.date()).num_days() / 7,
+                    Frequency::Monthly => months_between(anchor.date(), start.date()),
+                    Frequency::Yearly => months_between(anchor.date(), start.date()) / 12,
+                };
+                let mut k = (elapsed.max(1) - 1) as u32 / interval;
+                while nth(k) < start {
+                    k += 1;
+                }
+
+                nth(k)
+            }
+            Recurrence::Weekdays { interval, weekdays } => {
+                let anchor_week = anchor.date().week(Weekday::Mon).first_day();
+
+                (0..=7 * (*interval as u64 + 1))
+                    .filter_map(|offset| start.date().checked_add_days(Days::new(offset)))
+                    .map(|date| date.and_time(anchor.time()))
+                    .find(|candidate| {
+                        let week = candidate.date().week(Weekday::Mon).first_day();
+                        let weeks = (week - anchor_week).num_days() / 7;
+
+                        *candidate >= start
+                            && weeks % *interval as i64 == 0
+                            && weekdays.contains(&candidate.weekday())
+                    })
+                    .expect(OUT_OF_BOUNDS)
+            }
+            Recurrence::NthWeekday {
+                interval,
+                nth,
+                weekday,
+            } => {
+                let anchor_month = anchor.date().with_day(1).expect("every month has a 1st");
+                let elapsed = months_between(anchor_month, start.date()).max(0) as u32;
+                let mut k = elapsed / interval * interval;
+
+                loop {
+                    let month = anchor_month
+                        .checked_add_months(Months::new(k))
+                        .expect(OUT_OF_BOUNDS);
+                    if let Some(date) =
+                        nth_weekday_of_month(month.year(), month.month(), *nth, *weekday)
+                        && date.and_time(anchor.time()) >= start
+                    {
+                        break date.and_time(anchor.time());
+                    }
+
+                    k += interval;
+                }
+            }
+        };
+
+        from_local(&tz, next)
+    }
+
+    /// The due date coming right after `due_date`.
+    pub(crate) fn following<Tz: TimeZone>(
+        &self,
+        reference: &DateTime<Tz>,
+        due_date: &DateTime<Tz>,
+        month_end: MonthEndPolicy,
+    ) -> DateTime<Tz> {
+        self.next_on_or_after(
+            reference,
+            &(due_date.clone() + TimeDelta::seconds(1)),
+            month_end,
+        )
+    }
+
+    /// Canonical RRULE representation, as stored in the database.
+    pub(crate) fn to_rrule(&self) -> String {
+        let interval = |interval: u32| {
+            if interval == 1 {
+                String::new()
+            } else {
+                format!(";INTERVAL={interval}")
+            }
+        };
+
+        match self {
+            Recurrence::Every {
+                interval: i,
+                frequency,
+            } => {
+                let freq = match frequency {
+                    Frequency::Daily => "DAILY",
+                    Frequency::Weekly => "WEEKLY",
+                    Frequency::Monthly => "MONTHLY",
+                    Frequency::Yearly => "YEARLY",
+                };
+                format!("FREQ={}{}", freq, interval(*i))
+            }
+            Recurrence::Weekdays {
+                interval: i,
+                weekdays,
+            } => {
+                let days: Vec<_> = weekdays.iter().map(|d| weekday_code(*d)).collect();
+                format!("FREQ=WEEKLY{};BYDAY={}", interval(*i), days.join(","))
+            }
+            Recurrence::NthWeekday {
+                interval: i,
+                nth,
+                weekday,
+            } => format!(
+                "FREQ=MONTHLY{};BYDAY={}{}",
+                interval(*i),
+                nth,
+                weekday_code(*weekday)
+            ),
+        }
+    }
+
+    fn parse_rrule(rule: &str) -> Result<Self, String> {
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = None;
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let Some((key, value)) = part.split_once('=') else {
+                return Err(format!("invalid RRULE part: {part}"));
+            };
+
+            match key {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("unsupported RRULE frequency: {value}")),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|i| (1..=MAX_INTERVAL).contains(i))
+                        .ok_or(format!(
+                            "invalid RRULE interval: {value}. Expecting a number from 1 to {MAX_INTERVAL}"
+                        ))?
+                }
+                "BYDAY" => by_day = Some(value),
+                _ => return Err(format!("unsupported RRULE part: {key}")),
+            }
+        }
+
+        let Some(frequency) = frequency else {
+            return Err("RRULE is missing FREQ".to_string());
+        };
+
+        match (frequency, by_day) {
+            (frequency, None) => Ok(Recurrence::Every {
+                interval,
+                frequency,
+            }),
+            (Frequency::Weekly, Some(days)) => {
+                let mut weekdays = days
+                    .split(',')
+                    .map(|day| {
+                        parse_weekday_code(day).ok_or(format!("invalid RRULE weekday: {day}"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                weekdays.sort_by_key(|d| d.num_days_from_monday());
+                weekdays.dedup();
+
+                Ok(Recurrence::Weekdays { interval, weekdays })
+            }
+            (Frequency::Monthly, Some(day)) => {
+                if !day.is_ascii() {
+                    return Err(format!("invalid RRULE weekday: {day}"));
+                }
+                let split = day.len().saturating_sub(2);
+                let weekday = parse_weekday_code(&day[split..])
+                    .ok_or(format!("invalid RRULE weekday: {day}"))?;
+                let nth = day[..split]
+                    .trim_start_matches('+')
+                    .parse::<i8>()
+                    .ok()
+                    .filter(|n| *n != 0 && (-5..=5).contains(n))
+                    .ok_or(format!(
+                        "monthly BYDAY needs a position such as '2TU' or '-1FR', got: {day}"
+                    ))?;
+
+                Ok(Recurrence::NthWeekday {
+                    interval,
+                    nth,
+                    weekday,
+                })
+            }
+            (_, Some(_)) => Err("BYDAY is only supported with weekly or monthly rules".to_string()),
+        }
+    }
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Recurrence::Every {
+                interval,
+                frequency,
+            } => match (interval, frequency) {
+                (1, Frequency::Daily) => f.write_str("Daily"),
+                (1, Frequency::Weekly) => f.write_str("Weekly"),
+                (2, Frequency::Weekly) => f.write_str("Biweekly"),
+                (1, Frequency::Monthly) => f.write_str("Monthly"),
+                (2, Frequency::Monthly) => f.write_str("Bimonthly"),
+                (3, Frequency::Monthly) => f.write_str("Quarterly"),
+                (6, Frequency::Monthly) => f.write_str("Biannual"),
+                (1, Frequency::Yearly) => f.write_str("Yearly"),
+                (n, Frequency::Daily) => write!(f, "Every {n} days"),
+                (n, Frequency::Weekly) => write!(f, "Every {n} weeks"),
+                (n, Frequency::Monthly) => write!(f, "Every {n} months"),
+                (n, Frequency::Yearly) => write!(f, "Every {n} years"),
+            },
+            Recurrence::Weekdays { interval, weekdays } => {
+                let days: Vec<_> = weekdays.iter().map(|d| d.to_string()).collect();
+                if *interval == 1 {
+                    write!(f, "Weekly on {}", days.join(", "))
+                } else {
+                    write!(f, "Every {} weeks on {}", interval, days.join(", "))
+                }
+            }
+            Recurrence::NthWeekday {
+                interval,
+                nth,
+                weekday,
+            } => {
+                if *interval == 1 {
+                    write!(f, "Monthly on the {} {}", ordinal(*nth), weekday)
+                } else {
+                    write!(
+                        f,
+                        "Every {} months on the {} {}",
+                        interval,
+                        ordinal(*nth),
+                        weekday
+                    )
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    /// Accepts presets (`monthly`), `every 2 weeks`, weekday lists (`mon,thu`),
+    /// `2nd tue`, `last fri`, and RRULEs (`FREQ=MONTHLY;BYDAY=2TU`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.trim().to_ascii_uppercase();
+        let rule = upper.strip_prefix("RRULE:").unwrap_or(&upper);
+        if rule.contains("FREQ=") {
+            return Recurrence::parse_rrule(rule);
+        }
+
+        let every = |interval, frequency| {
+            Ok(Recurrence::Every {
+                interval,
+                frequency,
+            })
+        };
+        let lower = s.trim().to_ascii_lowercase();
+        match lower.as_str() {
+            "daily" => return every(1, Frequency::Daily),
+            "weekly" => return every(1, Frequency::Weekly),
+            "biweekly" | "fortnightly" => return every(2, Frequency::Weekly),
+            "monthly" => return every(1, Frequency::Monthly),
+            "bimonthly" => return every(2, Frequency::Monthly),
+            "trimonthly" | "quarterly" => return every(3, Frequency::Monthly),
+            "biannual" | "semiannual" => return every(6, Frequency::Monthly),
+            "yearly" | "annual" | "annually" => return every(1, Frequency::Yearly),
+            _ => {}
+        }
+
+        let words: Vec<_> = lower.split_whitespace().collect();
+        let unit = |word: &str| match word.trim_end_matches('s') {
+            "day" => Some(Frequency::Daily),
+            "week" => Some(Frequency::Weekly),
+            "month" => Some(Frequency::Monthly),
+            "year" => Some(Frequency::Yearly),
+            _ => None,
+        };
+        let nth = |word: &str| match word {
+            "1st" | "first" => Some(1),
+            "2nd" | "second" => Some(2),
+            "3rd" | "third" => Some(3),
+            "4th" | "fourth" => Some(4),
+            "5th" | "fifth" => Some(5),
+            "last" => Some(-1),
+            _ => None,
+        };
+
+        if let ["every", n, _] = words.as_slice()
+            && let Ok(n) = n.parse::<u32>()
+            && !(1..=MAX_INTERVAL).contains(&n)
+        {
+            return Err(format!(
+                "invalid interval: {n}. Expecting a number from 1 to {MAX_INTERVAL}"
+            ));
+        }
+
+        let parsed = match words.as_slice() {
+            ["every", word] => unit(word).map(|frequency| Recurrence::Every {
+                interval: 1,
+                frequency,
+            }),
+            ["every", n, word] => {
+                n.parse()
+                    .ok()
+                    .zip(unit(word))
+                    .map(|(interval, frequency)| Recurrence::Every {
+                        interval,
+                        frequency,
+                    })
+            }
+            [position, day] => {
+                nth(position)
+                    .zip(day.parse::<Weekday>().ok())
+                    .map(|(nth, weekday)| Recurrence::NthWeekday {
+                        interval: 1,
+                        nth,
+                        weekday,
+                    })
+            }
+            _ => None,
+        };
+        let weekdays = || {
+            lower
+                .split(',')
+                .map(|day| day.trim().parse::<Weekday>())
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .map(|mut weekdays| {
+                    weekdays.sort_by_key(|d| d.num_days_from_monday());
+                    weekdays.dedup();
+                    Recurrence::Weekdays {
+                        interval: 1,
+                        weekdays,
+                    }
+                })
+        };
+
+        parsed.or_else(weekdays).ok_or(format!(
+            "invalid recurrence: {s}. Expecting something like 'monthly', 'every 2 weeks', 'mon,thu', '2nd tue' or 'FREQ=MONTHLY;BYDAY=-1FR'"
+        ))
+    }
+}
+
+impl FromSql for Recurrence {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|_| rusqlite::types::FromSqlError::InvalidType)
+    }
+}
+
+impl ToSql for Recurrence {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.to_rrule()))
+    }
+}
+
+impl Serialize for Recurrence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_rrule())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, LocalResult, Utc};
+
+    use super::*;
+
+    fn date(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_parse_round_trips() {
+        for (text, rrule) in [
+            ("monthly", "FREQ=MONTHLY"),
+            ("Quarterly", "FREQ=MONTHLY;INTERVAL=3"),
+            ("every 10 days", "FREQ=DAILY;INTERVAL=10"),
+            ("every 1000 years", "FREQ=YEARLY;INTERVAL=1000"),
+            ("thu, mon", "FREQ=WEEKLY;BYDAY=MO,TH"),
+            ("2nd tuesday", "FREQ=MONTHLY;BYDAY=2TU"),
+            ("last fri", "FREQ=MONTHLY;BYDAY=-1FR"),
+            (
+                "RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=SA",
+                "FREQ=WEEKLY;INTERVAL=2;BYDAY=SA",
+            ),
+        ] {
+            let recurrence: Recurrence = text.parse().unwrap();
+            assert_eq!(recurrence.to_rrule(), rrule);
+            assert_eq!(rrule.parse::<Recurrence>().unwrap(), recurrence);
+        }
+
+        assert!("FREQ=MONTHLY;BYDAY=TU".parse::<Recurrence>().is_err());
+        assert!("FREQ=MONTHLY;BYDAY=ΩX".parse::<Recurrence>().is_err());
+        assert!("every 0 days".parse::<Recurrence>().is_err());
+        // Intervals too long to step through.
+        assert!("every 1000000000 days".parse::<Recurrence>().is_err());
+        assert!(
+            "FREQ=WEEKLY;INTERVAL=100000000;BYDAY=MO"
+                .parse::<Recurrence>()
+                .is_err()
+        );
+        assert!("FREQ=YEARLY;INTERVAL=1001".parse::<Recurrence>().is_err());
+        let longest: Recurrence = "every 1000 years".parse().unwrap();
+        assert_eq!(
+            longest.next_on_or_after(
+                &date("2020-01-01T00:00:01+00:00"),
+                &date("2026-10-17T00:00:00+00:00"),
+                MonthEndPolicy::Clamp
+            ),
+            date("3020-01-01T00:00:01+00:00")
+        );
+    }
+
+    #[test]
+    fn test_weekdays() {
+        let recurrence: Recurrence = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH".parse().unwrap();
+        // A Monday.
+        let reference = date("2025-12-01T00:00:01+00:00");
+
+        let mut due_date =
+            recurrence.next_on_or_after(&reference, &reference, MonthEndPolicy::Clamp);
+        let mut due_dates = vec![];
+        for _ in 0..4 {
+            due_dates.push(due_date);
+            due_date = recurrence.following(&reference, &due_date, MonthEndPolicy::Clamp);
+        }
+
+        assert_eq!(
+            due_dates,
+            vec![
+                date("2025-12-01T00:00:01+00:00"),
+                date("2025-12-04T00:00:01+00:00"),
+                date("2025-12-15T00:00:01+00:00"),
+                date("2025-12-18T00:00:01+00:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nth_weekday() {
+        let reference = date("2025-01-01T00:00:01+00:00");
+        let now = date("2025-03-12T00:00:00+00:00");
+
+        let second_tuesday: Recurrence = "2nd tue".parse().unwrap();
+        assert_eq!(
+            second_tuesday.next_on_or_after(&reference, &now, MonthEndPolicy::Clamp),
+            date("2025-04-08T00:00:01+00:00")
+        );
+
+        let last_friday: Recurrence = "last fri".parse().unwrap();
+        assert_eq!(
+            last_friday.next_on_or_after(&reference, &now, MonthEndPolicy::Clamp),
+            date("2025-03-28T00:00:01+00:00")
+        );
+    }
+
+    #[test]
+    fn test_month_end_policies() {
+        let monthly: Recurrence = "monthly".parse().unwrap();
+        let reference = date("2026-01-31T00:00:01+00:00");
+        let due_dates = |month_end| {
+            let mut due_date = monthly.next_on_or_after(&reference, &reference, month_end);
+            let mut due_dates = vec![];
+            for _ in 0..4 {
+                due_dates.push(due_date.date_naive().to_string());
+                due_date = monthly.following(&reference, &due_date, month_end);
+            }
+            due_dates
+        };
+
+        assert_eq!(
+            due_dates(MonthEndPolicy::Clamp),
+            ["2026-01-31", "2026-02-28", "2026-03-31", "2026-04-30"]
+        );
+        assert_eq!(
+            due_dates(MonthEndPolicy::Roll),
+            ["2026-01-31", "2026-03-01", "2026-03-31", "2026-05-01"]
+        );
+
+        let reference = date("2026-02-10T00:00:01+00:00");
+        let mut due_date =
+            monthly.next_on_or_after(&reference, &reference, MonthEndPolicy::LastDay);
+        let mut due_dates = vec![];
+        for _ in 0..3 {
+            due_dates.push(due_date.date_naive().to_string());
+            due_date = monthly.following(&reference, &due_date, MonthEndPolicy::LastDay);
+        }
+        assert_eq!(due_dates, ["2026-02-28", "2026-03-31", "2026-04-30"]);
+    }
+
+    /// São Paulo in 2018, where clocks jumped from midnight to 1am on November 4th.
+    #[derive(Clone)]
+    struct SaoPaulo2018;
+
+    impl TimeZone for SaoPaulo2018 {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            SaoPaulo2018
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let gap: NaiveDateTime = "2018-11-04T00:00:00".parse().unwrap();
+            if *local < gap {
+                LocalResult::Single(FixedOffset::west_opt(3 * 3600).unwrap())
+            } else if *local < gap + TimeDelta::hours(1) {
+                LocalResult::None
+            } else {
+                LocalResult::Single(FixedOffset::west_opt(2 * 3600).unwrap())
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let transition: NaiveDateTime = "2018-11-04T03:00:00".parse().unwrap();
+            let hours = if *utc < transition { 3 } else { 2 };
+
+            FixedOffset::west_opt(hours * 3600).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_from_local_in_dst_gap() {
+        let local = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+
+        assert_eq!(
+            from_local(&SaoPaulo2018, local("2018-11-03T00:00:01")).to_utc(),
+            date("2018-11-03T03:00:01+00:00")
+        );
+        // Midnight does not exist on the day DST starts, so the time moves to 1am.
+        assert_eq!(
+            from_local(&SaoPaulo2018, local("2018-11-04T00:00:01")).to_utc(),
+            date("2018-11-04T03:00:01+00:00")
+        );
+    }
+}
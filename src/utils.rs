@@ -1,139 +1,1497 @@
-use chrono::{Datelike, Days, Local, Months, Utc};
+use chrono::{Datelike, Days, Local, Months, NaiveDate, TimeZone, Utc};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use tabled::Tabled;
 
-use crate::model::{Expense, Payment, Periodicity};
+use crate::calendar::{BusinessDayRule, Calendar, MAX_ADJUSTMENT_DAYS};
+use crate::model::{
+    Amount, Budget, BudgetPeriod, BudgetTarget, Currency, Estimate, Expense, Money, Pause, Payment,
+    Split, SplitRule,
+};
+use crate::output::Columns;
+use crate::recurrence::{MonthEndPolicy, Recurrence, from_local};
 
-#[derive(Tabled)]
+fn display_amount(amount: &Option<Money>) -> String {
+    amount.map(|a| a.to_string()).unwrap_or("-".to_string())
+}
+
+fn display_last_payment(last_payment: &Option<NaiveDate>) -> String {
+    last_payment
+        .map(|date| date.to_string())
+        .unwrap_or("Not paid".to_string())
+}
+
+fn display_next_due_date(next_due_date: &Option<NaiveDate>) -> String {
+    next_due_date
+        .map(|date| date.to_string())
+        .unwrap_or("Paused".to_string())
+}
+
+fn display_days_left(days_left: &Option<i64>) -> String {
+    days_left
+        .map(|days| days.to_string())
+        .unwrap_or("-".to_string())
+}
+
+fn display_optional(value: &Option<&str>) -> String {
+    value.unwrap_or("-").to_string()
+}
+
+fn display_date(date: &Option<NaiveDate>) -> String {
+    date.map(|date| date.to_string()).unwrap_or("-".to_string())
+}
+
+fn display_is_paid(is_paid: &bool) -> &'static str {
+    if *is_paid { "✅" } else { "❌" }
+}
+
+/// Machine-readable formats get the bare decimal, with the currency in a column of its own.
+fn serialize_amount<S: Serializer>(
+    amount: &Option<Money>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match amount {
+        Some(money) => serializer.serialize_some(&money.amount.0),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn display_tags(tags: &&[String]) -> String {
+    if tags.is_empty() {
+        "-".to_string()
+    } else {
+        tags.join(", ")
+    }
+}
+
+/// Tags are joined in a single field, so that every format gets one value per column.
+fn serialize_tags<S: Serializer>(tags: &&[String], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&tags.join(","))
+}
+
+#[derive(Tabled, Serialize)]
 pub(crate) struct RowDisplay<'a> {
     pub(crate) expense_name: &'a str,
-    pub(crate) last_payment: String,
-    pub(crate) periodicity: Periodicity,
-    pub(crate) next_due_date: String,
-    pub(crate) days_left: i64,
-    is_paid: &'static str,
+    #[tabled(display = "display_last_payment")]
+    pub(crate) last_payment: Option<NaiveDate>,
+    #[tabled(rename = "periodicity")]
+    #[serde(skip)]
+    schedule: String,
+    #[tabled(skip)]
+    pub(crate) periodicity: &'a Recurrence,
+    #[tabled(rename = "amount")]
+    #[serde(skip)]
+    amount_text: String,
+    /// Estimated from past payments for variable expenses.
+    #[tabled(skip)]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) amount: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Option<Currency>,
+    #[tabled(skip)]
+    pub(crate) estimated: bool,
+    /// Missing while the expense is paused until resumed.
+    #[tabled(display = "display_next_due_date")]
+    pub(crate) next_due_date: Option<NaiveDate>,
+    #[tabled(display = "display_days_left")]
+    pub(crate) days_left: Option<i64>,
+    #[tabled(display = "display_is_paid")]
+    pub(crate) is_paid: bool,
+    pub(crate) overdue_count: usize,
+    #[serde(skip)]
+    progress: String,
+    /// Due dates paid, for installment plans and expenses with an end date.
+    #[tabled(skip)]
+    pub(crate) installments_paid: Option<usize>,
+    #[tabled(skip)]
+    pub(crate) installments: Option<usize>,
+    /// Shown as the section of the table rather than as a column.
+    #[tabled(skip)]
+    pub(crate) category: Option<&'a str>,
+    #[tabled(display = "display_tags")]
+    #[serde(serialize_with = "serialize_tags")]
+    pub(crate) tags: &'a [String],
+    /// Only worked out for `list --color`.
+    #[tabled(skip)]
+    #[serde(skip)]
+    pub(crate) over_budget: bool,
+}
+
+impl Columns for RowDisplay<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "expense_name",
+        "last_payment",
+        "periodicity",
+        "amount",
+        "currency",
+        "estimated",
+        "next_due_date",
+        "days_left",
+        "is_paid",
+        "overdue_count",
+        "installments_paid",
+        "installments",
+        "category",
+        "tags",
+    ];
+}
+
+#[derive(Tabled, Serialize)]
+pub(crate) struct HistoryRowDisplay<'a> {
+    pub(crate) id: i32,
+    pub(crate) expense_name: &'a str,
+    pub(crate) paid_at: NaiveDate,
+    pub(crate) due_date: NaiveDate,
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) amount: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Option<Currency>,
+    #[serde(skip)]
+    timing: String,
+    #[tabled(display = "display_optional")]
+    pub(crate) method: Option<&'a str>,
+    #[tabled(display = "display_optional")]
+    pub(crate) confirmation: Option<&'a str>,
+    #[tabled(display = "display_optional")]
+    pub(crate) note: Option<&'a str>,
+    #[tabled(display = "display_optional")]
+    pub(crate) paid_by: Option<&'a str>,
+    #[tabled(display = "display_optional")]
+    pub(crate) account: Option<&'a str>,
+    /// Negative when paid after the due date.
+    #[tabled(skip)]
+    pub(crate) days_early: i64,
+    #[tabled(skip)]
+    #[serde(skip)]
+    pub(crate) payment: &'a Payment,
+}
+
+impl Columns for HistoryRowDisplay<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "expense_name",
+        "paid_at",
+        "due_date",
+        "amount",
+        "currency",
+        "method",
+        "confirmation",
+        "note",
+        "paid_by",
+        "account",
+        "days_early",
+    ];
 }
 
-impl<'a> RowDisplay<'a> {
-    pub(crate) fn is_paid(&self) -> bool {
-        self.is_paid == "✅"
+fn describe_timing(days_early: i64) -> String {
+    let plural = |n: i64| if n == 1 { "" } else { "s" };
+
+    match days_early {
+        0 => "on due date".to_string(),
+        n if n > 0 => format!("{} day{} early", n, plural(n)),
+        n => format!("{} day{} late", -n, plural(-n)),
     }
 }
 
-fn get_next_due_date_aux(
+/// Due dates are computed on the wall clock of `now`'s timezone.
+fn get_next_due_date_aux<Tz: TimeZone>(
     reference: &chrono::DateTime<Utc>,
-    now: &chrono::DateTime<Utc>,
-    periodicity: Periodicity,
+    now: &chrono::DateTime<Tz>,
+    recurrence: &Recurrence,
+    month_end: MonthEndPolicy,
 ) -> chrono::DateTime<Utc> {
-    let mut reference = *reference;
-    if reference > *now {
-        return reference;
-    }
-
-    // Making the loops below O(1) on (now - reference). We could try calculating things and
-    // untangling issues like leap days, months having different durations etc,
-    // but I don't think it is worth it.
-    match periodicity {
-        Periodicity::Weekly => {
-            if reference.year() < now.year() - 1
-                && let Some(new_ref) = reference.with_year(now.year() - 1)
-            {
-                for i in 0..7 {
-                    if let Some(sub_ref) = new_ref.checked_sub_days(Days::new(i))
-                        && sub_ref.weekday() == reference.weekday()
-                    {
-                        reference = sub_ref;
-                        break;
-                    }
-                }
-            };
+    let reference = reference.with_timezone(&now.timezone());
+
+    recurrence
+        .next_on_or_after(&reference, now, month_end)
+        .to_utc()
+}
+
+/// First instant of `date` in the local timezone.
+pub(crate) fn start_of_day(date: NaiveDate) -> chrono::DateTime<Utc> {
+    from_local(
+        &Local,
+        date.and_hms_opt(0, 0, 0).expect("arguments are valid"),
+    )
+    .to_utc()
+}
+
+/// When `due_date` can actually be paid, once moved by the business-day rule of the expense.
+fn payable_at<Tz: TimeZone>(
+    expense: &Expense,
+    calendar: &Calendar,
+    due_date: &chrono::DateTime<Tz>,
+) -> chrono::DateTime<Tz> {
+    let date = due_date.date_naive();
+    let payable_date = expense.business_day.adjust(date, calendar);
+
+    if payable_date == date {
+        due_date.clone()
+    } else {
+        from_local(&due_date.timezone(), payable_date.and_time(due_date.time()))
+    }
+}
+
+/// The (local) day `due_date` can actually be paid on.
+pub(crate) fn get_payable_date(
+    expense: &Expense,
+    calendar: &Calendar,
+    due_date: &chrono::DateTime<Utc>,
+) -> NaiveDate {
+    payable_at(expense, calendar, &due_date.with_timezone(&Local)).date_naive()
+}
+
+/// The due date of the expense falling on the (local) `date`, if there is one. Due dates moved
+/// to `date` by the business-day rule are found as well.
+pub(crate) fn get_due_date_on(
+    expense: &Expense,
+    calendar: &Calendar,
+    date: NaiveDate,
+) -> Option<chrono::DateTime<Utc>> {
+    let candidate = get_next_due_date_aux(
+        &expense.due_date_reference,
+        &start_of_day(date).with_timezone(&Local),
+        &expense.recurrence,
+        expense.month_end,
+    );
+    if candidate.with_timezone(&Local).date_naive() == date {
+        return Some(candidate).filter(|due_date| {
+            !is_paused(expense, due_date) && !is_past_the_end(expense, due_date)
+        });
+    }
+
+    let reference = expense.due_date_reference.with_timezone(&Local);
+    let mut due_date = get_next_due_date_aux(
+        &expense.due_date_reference,
+        &start_of_day(date - Days::new(MAX_ADJUSTMENT_DAYS)).with_timezone(&Local),
+        &expense.recurrence,
+        expense.month_end,
+    )
+    .with_timezone(&Local);
+    while due_date.date_naive() <= date + Days::new(MAX_ADJUSTMENT_DAYS) {
+        if payable_at(expense, calendar, &due_date).date_naive() == date {
+            return Some(due_date.to_utc()).filter(|due_date| {
+                !is_paused(expense, due_date) && !is_past_the_end(expense, due_date)
+            });
         }
-        _ => {
-            if reference.year() < now.year() - 1
-                && let Some(new_ref) = reference.with_year(now.year() - 1)
-            {
-                reference = new_ref;
-            };
+        due_date = expense
+            .recurrence
+            .following(&reference, &due_date, expense.month_end);
+    }
+
+    None
+}
+
+/// Up to `count` consecutive due dates of the expense, starting at `first`. Fewer are returned
+/// if the expense ends before.
+pub(crate) fn get_consecutive_due_dates(
+    expense: &Expense,
+    first: &chrono::DateTime<Utc>,
+    count: usize,
+) -> Vec<chrono::DateTime<Utc>> {
+    let reference = expense.due_date_reference.with_timezone(&Local);
+
+    std::iter::successors(Some(first.with_timezone(&Local)), |due_date| {
+        Some(
+            expense
+                .recurrence
+                .following(&reference, due_date, expense.month_end),
+        )
+    })
+    .take_while(|due_date| !is_paused_until_resumed(expense, due_date))
+    .filter(|due_date| !is_paused(expense, due_date))
+    .take(count)
+    .take_while(|due_date| !is_past_the_end(expense, due_date))
+    .map(|due_date| due_date.to_utc())
+    .collect()
+}
+
+fn has_end(expense: &Expense) -> bool {
+    expense.installments.is_some() || expense.until.is_some()
+}
+
+fn is_paused<Tz: TimeZone>(expense: &Expense, due_date: &chrono::DateTime<Tz>) -> bool {
+    expense
+        .pauses
+        .iter()
+        .any(|pause| pause.contains(due_date.date_naive()))
+}
+
+/// Whether the expense is paused from `due_date` on, with no date set to resume it.
+fn is_paused_until_resumed<Tz: TimeZone>(
+    expense: &Expense,
+    due_date: &chrono::DateTime<Tz>,
+) -> bool {
+    expense
+        .pauses
+        .iter()
+        .any(|pause| pause.end_date.is_none() && pause.start_date <= due_date.date_naive())
+}
+
+/// Every due date of the expense, from the first one on or after its reference date, skipping
+/// those it is paused on. Ends after its installments or at its end date, if it has any.
+fn get_due_dates_aux<'a, Tz: TimeZone + 'a>(
+    expense: &'a Expense,
+    tz: &Tz,
+) -> impl Iterator<Item = chrono::DateTime<Tz>> + 'a {
+    let reference = expense.due_date_reference.with_timezone(tz);
+    let first = expense
+        .recurrence
+        .next_on_or_after(&reference, &reference, expense.month_end);
+
+    std::iter::successors(Some(first), move |due_date| {
+        Some(
+            expense
+                .recurrence
+                .following(&reference, due_date, expense.month_end),
+        )
+    })
+    .take_while(|due_date| !is_paused_until_resumed(expense, due_date))
+    .filter(|due_date| !is_paused(expense, due_date))
+    .take(expense.installments.map_or(usize::MAX, |n| n as usize))
+    .take_while(|due_date| {
+        expense
+            .until
+            .is_none_or(|until| due_date.date_naive() <= until)
+    })
+}
+
+/// Whether `due_date` comes after the last installment or the end date of the expense.
+fn is_past_the_end<Tz: TimeZone>(expense: &Expense, due_date: &chrono::DateTime<Tz>) -> bool {
+    if expense
+        .until
+        .is_some_and(|until| due_date.date_naive() > until)
+    {
+        return true;
+    }
+
+    expense.installments.is_some_and(|installments| {
+        get_due_dates_aux(expense, &due_date.timezone())
+            .nth(installments as usize - 1)
+            .is_some_and(|last| *due_date > last)
+    })
+}
+
+/// The first due date of the expense that can still be paid at `now`, if it has not ended nor
+/// been paused until resumed. Due dates are moved by the business-day rule of the expense on top
+/// of the schedule of `get_next_due_date_aux`.
+fn get_next_payable_due_date_aux<Tz: TimeZone>(
+    expense: &Expense,
+    calendar: &Calendar,
+    now: &chrono::DateTime<Tz>,
+) -> Option<chrono::DateTime<Utc>> {
+    let tz = now.timezone();
+    let reference = expense.due_date_reference.with_timezone(&tz);
+    // A due date already gone may still be payable if it was moved forward.
+    let lookback = match expense.business_day {
+        BusinessDayRule::None => Days::new(0),
+        _ => Days::new(MAX_ADJUSTMENT_DAYS),
+    };
+    let mut due_date = get_next_due_date_aux(
+        &expense.due_date_reference,
+        &(now.clone() - lookback),
+        &expense.recurrence,
+        expense.month_end,
+    )
+    .with_timezone(&tz);
+
+    while payable_at(expense, calendar, &due_date) < *now || is_paused(expense, &due_date) {
+        if is_paused_until_resumed(expense, &due_date) {
+            return None;
         }
+        due_date = expense
+            .recurrence
+            .following(&reference, &due_date, expense.month_end);
     }
 
-    match periodicity {
-        Periodicity::Weekly => {
-            while reference < *now {
-                reference = reference.checked_add_days(Days::new(7)).expect("should not be reaching out of bounds for time operations. Chosen day might be invalid as periodic input (E.g., monthly and 31), or you might be in the FAR future? o_o");
-            }
+    (!is_past_the_end(expense, &due_date)).then(|| due_date.to_utc())
+}
 
-            reference
+/// Every due date of the expense from its creation (or its reference, if later) that could be
+/// paid before `now`, excluding those covered by a payment or paused. Installment plans and
+/// expenses with an end date are checked from their first due date instead.
+fn get_overdue_dates_aux<Tz: TimeZone>(
+    expense: &Expense,
+    payments: &[Payment],
+    calendar: &Calendar,
+    now: &chrono::DateTime<Tz>,
+) -> Vec<chrono::DateTime<Utc>> {
+    let tz = now.timezone();
+    let reference = expense.due_date_reference.with_timezone(&tz);
+    let start = if has_end(expense) {
+        reference.clone()
+    } else {
+        expense.created_at.with_timezone(&tz)
+    };
+    let mut due_date = expense
+        .recurrence
+        .next_on_or_after(&reference, &start, expense.month_end);
+    let mut overdue = Vec::new();
+
+    while payable_at(expense, calendar, &due_date) < *now && !is_past_the_end(expense, &due_date) {
+        if !is_paid(expense, payments, &due_date.to_utc()) && !is_paused(expense, &due_date) {
+            overdue.push(due_date.to_utc());
         }
-        Periodicity::Monthly => {
-            while reference < *now {
-                reference = reference.checked_add_months(Months::new(1)).expect("should not be reaching out of bounds for time operations. Chosen day might be invalid as periodic input (E.g., monthly and 31), or you might be in the FAR future? o_o");
-            }
 
-            reference
+        due_date = expense
+            .recurrence
+            .following(&reference, &due_date, expense.month_end);
+    }
+
+    overdue
+}
+
+pub(crate) fn get_overdue_dates(
+    expense: &Expense,
+    payments: &[Payment],
+    calendar: &Calendar,
+) -> Vec<chrono::DateTime<Utc>> {
+    get_overdue_dates_aux(expense, payments, calendar, &Local::now())
+}
+
+pub(crate) fn get_next_due_date(
+    expense: &Expense,
+    calendar: &Calendar,
+) -> Option<chrono::DateTime<Utc>> {
+    get_next_payable_due_date_aux(expense, calendar, &Local::now())
+}
+
+/// Due dates of the expense that can be paid from `now` up to `until`, in the timezone of `now`,
+/// excluding those covered by a payment or paused.
+fn get_upcoming_due_dates_aux<Tz: TimeZone>(
+    expense: &Expense,
+    payments: &[Payment],
+    calendar: &Calendar,
+    now: &chrono::DateTime<Tz>,
+    until: NaiveDate,
+) -> Vec<chrono::DateTime<Utc>> {
+    let tz = now.timezone();
+    let reference = expense.due_date_reference.with_timezone(&tz);
+    let Some(first) = get_next_payable_due_date_aux(expense, calendar, now) else {
+        return Vec::new();
+    };
+
+    std::iter::successors(Some(first.with_timezone(&tz)), |due_date| {
+        Some(
+            expense
+                .recurrence
+                .following(&reference, due_date, expense.month_end),
+        )
+    })
+    .take_while(|due_date| !is_paused_until_resumed(expense, due_date))
+    .take_while(|due_date| payable_at(expense, calendar, due_date).date_naive() <= until)
+    .take_while(|due_date| !is_past_the_end(expense, due_date))
+    .filter(|due_date| !is_paused(expense, due_date))
+    .map(|due_date| due_date.to_utc())
+    .filter(|due_date| !is_paid(expense, payments, due_date))
+    .collect()
+}
+
+/// Due dates of the expense that can be paid between `from` and `until`, both included, in the
+/// timezone `tz`, whether paid or not. Paused ones are skipped.
+fn get_due_dates_between_aux<Tz: TimeZone>(
+    expense: &Expense,
+    calendar: &Calendar,
+    tz: &Tz,
+    from: NaiveDate,
+    until: NaiveDate,
+) -> Vec<chrono::DateTime<Utc>> {
+    let reference = expense.due_date_reference.with_timezone(tz);
+    let lookback = from - Days::new(MAX_ADJUSTMENT_DAYS);
+    let start = from_local(
+        tz,
+        lookback.and_hms_opt(0, 0, 0).expect("arguments are valid"),
+    );
+    let first = expense.recurrence.next_on_or_after(
+        &reference,
+        &start.max(reference.clone()),
+        expense.month_end,
+    );
+
+    std::iter::successors(Some(first), |due_date| {
+        Some(
+            expense
+                .recurrence
+                .following(&reference, due_date, expense.month_end),
+        )
+    })
+    .take_while(|due_date| due_date.date_naive() <= until + Days::new(MAX_ADJUSTMENT_DAYS))
+    .take_while(|due_date| !is_past_the_end(expense, due_date))
+    .filter(|due_date| !is_paused(expense, due_date))
+    .filter(|due_date| {
+        let payable_date = payable_at(expense, calendar, due_date).date_naive();
+        from <= payable_date && payable_date <= until
+    })
+    .map(|due_date| due_date.to_utc())
+    .collect()
+}
+
+pub(crate) fn is_paid(
+    expense: &Expense,
+    payments: &[Payment],
+    due_date: &chrono::DateTime<Utc>,
+) -> bool {
+    payments
+        .iter()
+        .any(|p| p.expense_name == expense.name && p.due_date_of_expense == *due_date)
+}
+
+/// Number of latest payments averaged by `Estimate::Average`.
+const TRAILING_PAYMENTS: usize = 3;
+
+/// Expected amount of the expense on `due_date`, and whether it was estimated. Variable expenses
+/// are estimated from their payments in the currency of the latest one, falling back to the
+/// trailing average when there is no payment for the same month of the previous year.
+///
+/// Until first paid, variable expenses use their set amount instead. As it was given rather than
+/// estimated, it is not flagged as an estimate.
+pub(crate) fn estimate_amount(
+    expense: &Expense,
+    payments: &[Payment],
+    due_date: &chrono::DateTime<Utc>,
+) -> (Option<Money>, bool) {
+    let Some(estimate) = expense.estimate else {
+        return (expense.amount, false);
+    };
+
+    let mut paid: Vec<(NaiveDate, Money)> = payments
+        .iter()
+        .filter(|p| p.expense_name == expense.name)
+        .filter_map(|p| {
+            let due_date = p.due_date_of_expense.with_timezone(&Local).date_naive();
+            p.amount.map(|amount| (due_date, amount))
+        })
+        .collect();
+    paid.sort_by_key(|(due_date, _)| std::cmp::Reverse(*due_date));
+    let Some((_, latest)) = paid.first().copied() else {
+        return (expense.amount, false);
+    };
+    paid.retain(|(_, amount)| amount.currency == latest.currency);
+
+    let average = || {
+        let trailing: Vec<_> = paid.iter().take(TRAILING_PAYMENTS).collect();
+        let total: Decimal = trailing.iter().map(|(_, amount)| amount.amount.0).sum();
+
+        Money {
+            amount: Amount((total / Decimal::from(trailing.len())).round_dp(2)),
+            currency: latest.currency,
         }
-        Periodicity::Bimonthly => {
-            while reference < *now {
-                reference = reference.checked_add_months(Months::new(2)).expect("should not be reaching out of bounds for time operations. Chosen day might be invalid as periodic input (E.g., monthly and 31), or you might be in the FAR future? o_o");
-            }
+    };
 
-            reference
+    let amount = match estimate {
+        Estimate::Last => latest,
+        Estimate::Average => average(),
+        Estimate::SameMonth => {
+            let due_date = due_date.with_timezone(&Local);
+            paid.iter()
+                .find(|(paid_due_date, _)| {
+                    paid_due_date.year() == due_date.year() - 1
+                        && paid_due_date.month() == due_date.month()
+                })
+                .map(|(_, amount)| *amount)
+                .unwrap_or_else(average)
         }
-        Periodicity::Trimonthly => {
-            while reference < *now {
-                reference = reference.checked_add_months(Months::new(3)).expect("should not be reaching out of bounds for time operations. Chosen day might be invalid as periodic input (E.g., monthly and 31), or you might be in the FAR future? o_o");
-            }
+    };
+
+    (Some(amount), true)
+}
+
+fn describe_amount(amount: Option<Money>, estimated: bool) -> String {
+    match amount {
+        Some(amount) if estimated => format!("{amount} (est.)"),
+        _ => display_amount(&amount),
+    }
+}
+
+/// The due date paid when none is chosen: the next one for open-ended expenses, and the earliest
+/// unpaid one for installment plans and expenses with an end date.
+pub(crate) fn get_due_date_to_pay(
+    expense: &Expense,
+    payments: &[Payment],
+    calendar: &Calendar,
+) -> Option<chrono::DateTime<Utc>> {
+    if has_end(expense) {
+        get_due_dates_aux(expense, &Local)
+            .map(|due_date| due_date.to_utc())
+            .find(|due_date| !is_paid(expense, payments, due_date))
+    } else {
+        get_next_due_date(expense, calendar)
+    }
+}
+
+/// How many due dates of the expense are paid, out of how many, if it has an end.
+fn get_progress(expense: &Expense, payments: &[Payment]) -> Option<(usize, usize)> {
+    if !has_end(expense) {
+        return None;
+    }
+
+    let due_dates: Vec<_> = get_due_dates_aux(expense, &Local).collect();
+    let paid = due_dates
+        .iter()
+        .filter(|due_date| is_paid(expense, payments, &due_date.to_utc()))
+        .count();
 
-            reference
+    // Installments after a pause with no end are still to be paid, once resumed.
+    let total = match expense.installments {
+        Some(installments) if expense.pauses.iter().any(|p| p.end_date.is_none()) => {
+            installments as usize
         }
-        Periodicity::Quarterly => {
-            while reference < *now {
-                reference = reference.checked_add_months(Months::new(4)).expect("should not be reaching out of bounds for time operations. Chosen day might be invalid as periodic input (E.g., monthly and 31), or you might be in the FAR future? o_o");
-            }
+        _ => due_dates.len(),
+    };
+
+    Some((paid, total))
+}
+
+fn describe_schedule(expense: &Expense) -> String {
+    let schedule = match expense.installments {
+        Some(1) => "Once".to_string(),
+        _ => expense.recurrence.to_string(),
+    };
+    let today = Local::now().date_naive();
+
+    match expense.pauses.iter().find(|pause| pause.contains(today)) {
+        Some(Pause {
+            end_date: Some(end_date),
+            ..
+        }) => format!("{schedule} (paused until {end_date})"),
+        Some(_) => format!("{schedule} (paused)"),
+        None => schedule,
+    }
+}
 
-            reference
+/// Labelled fields of the expense for `show`, leaving out the ones that are not set.
+pub(crate) fn describe_expense(
+    expense: &Expense,
+    split: Option<&Split>,
+    payments: &[Payment],
+    calendar: &Calendar,
+) -> Vec<(&'static str, String)> {
+    let mut fields = vec![("Name", expense.name.clone())];
+    if expense.income {
+        fields.push(("Type", "Income".to_string()));
+    }
+    if let Some(category) = &expense.category {
+        fields.push(("Category", category.clone()));
+    }
+    if !expense.tags.is_empty() {
+        fields.push(("Tags", expense.tags.join(", ")));
+    }
+    fields.push(("Schedule", describe_schedule(expense)));
+    if let Some((paid, total)) = get_progress(expense, payments) {
+        fields.push(("Progress", format!("{paid}/{total} paid")));
+    }
+    match (expense.estimate, expense.amount) {
+        (Some(estimate), _) => {
+            let amount = get_due_date_to_pay(expense, payments, calendar)
+                .and_then(|due_date| estimate_amount(expense, payments, &due_date).0);
+            let amount = amount.map_or("variable".to_string(), |amount| amount.to_string());
+            fields.push(("Amount", format!("{amount} (estimated: {estimate})")));
+        }
+        (None, Some(amount)) => fields.push(("Amount", amount.to_string())),
+        (None, None) => {}
+    }
+    if let Some(split) = split {
+        let shares: Vec<_> = split
+            .shares
+            .iter()
+            .map(|(member, share)| match (split.rule, share) {
+                (SplitRule::Percentage, Some(share)) => format!("{member} {}%", share.0),
+                (SplitRule::Fixed, Some(share)) => format!("{member} {share}"),
+                _ => member.clone(),
+            })
+            .collect();
+        fields.push(("Split", format!("{} ({})", shares.join(", "), split.rule)));
+    }
+    for (label, value) in [
+        ("Account", &expense.account),
+        ("Payee", &expense.payee),
+        ("Reference", &expense.reference),
+        ("URL", &expense.url),
+        ("Notes", &expense.notes),
+    ] {
+        if let Some(value) = value {
+            fields.push((label, value.clone()));
         }
-        Periodicity::Biannual => {
-            while reference < *now {
-                reference = reference.checked_add_months(Months::new(6)).expect("should not be reaching out of bounds for time operations. Chosen day might be invalid as periodic input (E.g., monthly and 31), or you might be in the FAR future? o_o");
+    }
+    match expense.archived_at {
+        Some(archived_at) => fields.push((
+            "Archived",
+            archived_at.with_timezone(&Local).date_naive().to_string(),
+        )),
+        None => {
+            if let Some(due_date) = get_due_date_to_pay(expense, payments, calendar) {
+                fields.push((
+                    "Next due date",
+                    get_payable_date(expense, calendar, &due_date).to_string(),
+                ));
+            }
+            let overdue = get_overdue_dates(expense, payments, calendar);
+            if !overdue.is_empty() {
+                fields.push(("Overdue", overdue.len().to_string()));
             }
-
-            reference
         }
     }
+
+    fields
 }
 
-pub(crate) fn get_next_due_date(
-    reference: &chrono::DateTime<Utc>,
-    periodicity: Periodicity,
-) -> chrono::DateTime<Utc> {
-    get_next_due_date_aux(reference, &Utc::now(), periodicity)
+#[derive(Tabled, Serialize)]
+pub(crate) struct OverdueRowDisplay<'a> {
+    pub(crate) expense_name: &'a str,
+    pub(crate) due_date: NaiveDate,
+    pub(crate) days_overdue: i64,
+    #[tabled(rename = "amount")]
+    #[serde(skip)]
+    amount_text: String,
+    /// Estimated from past payments for variable expenses.
+    #[tabled(skip)]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) amount: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Option<Currency>,
+    #[tabled(skip)]
+    pub(crate) estimated: bool,
 }
 
-pub(crate) fn generate_rows<'a>(entries: &'a [(Expense, Option<Payment>)]) -> Vec<RowDisplay<'a>> {
+impl Columns for OverdueRowDisplay<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "expense_name",
+        "due_date",
+        "days_overdue",
+        "amount",
+        "currency",
+        "estimated",
+    ];
+}
+
+pub(crate) fn generate_overdue_rows<'a>(
+    expenses: &'a [Expense],
+    payments: &[Payment],
+    calendar: &Calendar,
+) -> Vec<OverdueRowDisplay<'a>> {
+    let today = Local::now().date_naive();
+    let mut rows: Vec<_> = expenses
+        .iter()
+        .flat_map(|expense| {
+            get_overdue_dates(expense, payments, calendar)
+                .into_iter()
+                .map(move |due_date| {
+                    let (amount, estimated) = estimate_amount(expense, payments, &due_date);
+                    let due_date = get_payable_date(expense, calendar, &due_date);
+
+                    OverdueRowDisplay {
+                        expense_name: &expense.name,
+                        due_date,
+                        days_overdue: today.signed_duration_since(due_date).num_days(),
+                        amount_text: describe_amount(amount, estimated),
+                        amount,
+                        currency: amount.map(|m| m.currency),
+                        estimated,
+                    }
+                })
+        })
+        .collect();
+    rows.sort_by_key(|row| row.due_date);
+
+    rows
+}
+
+#[derive(Tabled, Serialize)]
+pub(crate) struct ForecastRowDisplay<'a> {
+    pub(crate) due_date: NaiveDate,
+    pub(crate) expense_name: &'a str,
+    #[tabled(rename = "amount")]
+    #[serde(skip)]
+    amount_text: String,
+    /// Estimated from past payments for variable expenses.
+    #[tabled(skip)]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) amount: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Option<Currency>,
+    #[tabled(skip)]
+    pub(crate) estimated: bool,
+}
+
+impl Columns for ForecastRowDisplay<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "due_date",
+        "expense_name",
+        "amount",
+        "currency",
+        "estimated",
+    ];
+}
+
+/// Every unpaid occurrence of the expenses from now until the (local) `until` date, by the day it
+/// can be paid on.
+pub(crate) fn generate_forecast_rows<'a>(
+    expenses: &'a [Expense],
+    payments: &[Payment],
+    calendar: &Calendar,
+    until: NaiveDate,
+) -> Vec<ForecastRowDisplay<'a>> {
+    let now = Local::now();
+    let mut rows: Vec<_> = expenses
+        .iter()
+        .flat_map(|expense| {
+            get_upcoming_due_dates_aux(expense, payments, calendar, &now, until)
+                .into_iter()
+                .map(move |due_date| {
+                    let (amount, estimated) = estimate_amount(expense, payments, &due_date);
+
+                    ForecastRowDisplay {
+                        due_date: get_payable_date(expense, calendar, &due_date),
+                        expense_name: &expense.name,
+                        amount_text: describe_amount(amount, estimated),
+                        amount,
+                        currency: amount.map(|m| m.currency),
+                        estimated,
+                    }
+                })
+        })
+        .collect();
+    rows.sort_by(|a, b| (a.due_date, a.expense_name).cmp(&(b.due_date, b.expense_name)));
+
+    rows
+}
+
+/// Installment plans and expenses with an end date are left out once fully paid. After their
+/// last due date, their earliest unpaid one is shown as the next one. Expenses paused until
+/// resumed have none.
+pub(crate) fn generate_rows<'a>(
+    entries: &'a [(Expense, Option<Payment>)],
+    payments: &[Payment],
+    calendar: &Calendar,
+) -> Vec<RowDisplay<'a>> {
     entries
         .iter()
-        .map(|(expense, payment)| {
-            let next_due_date = get_next_due_date(&expense.due_date_reference, expense.periodicity);
+        .filter_map(|(expense, payment)| {
+            let progress = get_progress(expense, payments);
+            if progress.is_some_and(|(paid, total)| paid == total) {
+                return None;
+            }
+            let overdue = get_overdue_dates(expense, payments, calendar);
+            let next_due_date = get_next_due_date(expense, calendar).or(overdue.first().copied());
+            let payable_at = next_due_date
+                .map(|due_date| payable_at(expense, calendar, &due_date.with_timezone(&Local)));
+            let (amount, estimated) = match next_due_date {
+                Some(due_date) => estimate_amount(expense, payments, &due_date),
+                None => (expense.amount, false),
+            };
 
-            RowDisplay {
+            Some(RowDisplay {
                 expense_name: &expense.name,
                 last_payment: payment
                     .as_ref()
-                    .map(|p| p.paid_at.with_timezone(&Local).date_naive().to_string())
-                    .unwrap_or("Not paid".to_string()),
-                periodicity: expense.periodicity,
-                next_due_date: next_due_date.with_timezone(&Local).date_naive().to_string(),
-                days_left: next_due_date.signed_duration_since(Utc::now()).num_days(),
-                is_paid: payment
-                    .as_ref()
-                    .map(|p| {
-                        if p.due_date_of_expense == next_due_date {
-                            "✅"
-                        } else {
-                            "❌"
-                        }
-                    })
-                    .unwrap_or("❌"),
+                    .map(|p| p.paid_at.with_timezone(&Local).date_naive()),
+                schedule: describe_schedule(expense),
+                periodicity: &expense.recurrence,
+                amount_text: describe_amount(amount, estimated),
+                amount,
+                currency: amount.map(|m| m.currency),
+                estimated,
+                next_due_date: payable_at.map(|payable_at| payable_at.date_naive()),
+                days_left: payable_at
+                    .map(|payable_at| payable_at.signed_duration_since(Utc::now()).num_days()),
+                is_paid: next_due_date
+                    .is_some_and(|due_date| is_paid(expense, payments, &due_date)),
+                overdue_count: overdue.len(),
+                progress: progress
+                    .map(|(paid, total)| format!("{paid}/{total} paid"))
+                    .unwrap_or("-".to_string()),
+                installments_paid: progress.map(|(paid, _)| paid),
+                installments: progress.map(|(_, total)| total),
+                category: expense.category.as_deref(),
+                tags: &expense.tags,
+                over_budget: false,
+            })
+        })
+        .collect()
+}
+
+/// Length of the periods payments are grouped in by `report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ReportPeriod {
+    Month,
+    Quarter,
+    Year,
+}
+
+impl ReportPeriod {
+    fn months(&self) -> u32 {
+        match self {
+            ReportPeriod::Month => 1,
+            ReportPeriod::Quarter => 3,
+            ReportPeriod::Year => 12,
+        }
+    }
+
+    /// First day of the period `date` falls in.
+    fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        let month = date.month0() - date.month0() % self.months() + 1;
+
+        NaiveDate::from_ymd_opt(date.year(), month, 1).expect("arguments are valid")
+    }
+
+    fn label(&self, start: NaiveDate) -> String {
+        match self {
+            ReportPeriod::Month => start.format("%Y-%m").to_string(),
+            ReportPeriod::Quarter => format!("{}-Q{}", start.year(), start.month0() / 3 + 1),
+            ReportPeriod::Year => start.year().to_string(),
+        }
+    }
+}
+
+fn display_change(change: &Option<Decimal>) -> String {
+    change
+        .map(|change| format!("{:+.2}", change))
+        .unwrap_or("-".to_string())
+}
+
+fn display_change_percent(change_percent: &Option<Decimal>) -> String {
+    change_percent
+        .map(|percent| format!("{:+.1}%", percent))
+        .unwrap_or("-".to_string())
+}
+
+#[derive(Tabled, Serialize)]
+pub(crate) struct ReportRowDisplay {
+    pub(crate) period: String,
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) total: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Currency,
+    pub(crate) payments: usize,
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) average: Option<Money>,
+    /// Difference from the total of the previous period in the same currency.
+    #[tabled(display = "display_change")]
+    pub(crate) change: Option<Decimal>,
+    /// Missing when nothing was spent in that currency in the previous period.
+    #[tabled(display = "display_change_percent")]
+    pub(crate) change_percent: Option<Decimal>,
+}
+
+impl Columns for ReportRowDisplay {
+    const COLUMNS: &'static [&'static str] = &[
+        "period",
+        "total",
+        "currency",
+        "payments",
+        "average",
+        "change",
+        "change_percent",
+    ];
+}
+
+/// Totals of the amounts paid on each day, per period and currency. Periods without payments are
+/// left out, but still count as spending nothing for the change of the next one.
+pub(crate) fn generate_report_rows(
+    payments: &[(NaiveDate, Money)],
+    by: ReportPeriod,
+) -> Vec<ReportRowDisplay> {
+    let mut totals: BTreeMap<(NaiveDate, Currency), (Decimal, usize)> = BTreeMap::new();
+    for (paid_at, money) in payments {
+        let (total, count) = totals
+            .entry((by.start_of(*paid_at), money.currency))
+            .or_insert((Decimal::ZERO, 0));
+        *total += money.amount.0;
+        *count += 1;
+    }
+    let first = totals.keys().map(|(start, _)| *start).min();
+
+    totals
+        .iter()
+        .map(|(&(start, currency), &(total, count))| {
+            let previous_start = start - Months::new(by.months());
+            let previous = match totals.get(&(previous_start, currency)) {
+                Some((previous, _)) => Some(*previous),
+                // Nothing to compare against before the first payment.
+                None if first.is_some_and(|first| previous_start >= first) => Some(Decimal::ZERO),
+                None => None,
+            };
+            let money = |amount: Decimal| Money {
+                amount: Amount(amount.round_dp(2)),
+                currency,
+            };
+
+            ReportRowDisplay {
+                period: by.label(start),
+                total: Some(money(total)),
+                currency,
+                payments: count,
+                average: Some(money(total / Decimal::from(count))),
+                change: previous.map(|previous| (total - previous).round_dp(2)),
+                change_percent: previous
+                    .filter(|previous| !previous.is_zero())
+                    .map(|previous| {
+                        ((total - previous) / previous * Decimal::ONE_HUNDRED).round_dp(1)
+                    }),
+            }
+        })
+        .collect()
+}
+
+fn display_used(used: &Option<Decimal>) -> String {
+    used.map(|used| format!("{:.0}%", used))
+        .unwrap_or("-".to_string())
+}
+
+fn display_over_budget(over_budget: &bool) -> &'static str {
+    if *over_budget { "❌" } else { "✅" }
+}
+
+#[derive(Tabled, Serialize)]
+pub(crate) struct BudgetRowDisplay<'a> {
+    pub(crate) budget: String,
+    pub(crate) period: String,
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) limit: Option<Money>,
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) spent: Option<Money>,
+    /// Negative once over budget.
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) remaining: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Currency,
+    /// Percentage of the limit spent. Missing for limits of zero.
+    #[tabled(display = "display_used")]
+    pub(crate) used: Option<Decimal>,
+    #[tabled(display = "display_over_budget")]
+    pub(crate) over_budget: bool,
+    #[tabled(skip)]
+    #[serde(skip)]
+    pub(crate) target: &'a BudgetTarget,
+}
+
+impl Columns for BudgetRowDisplay<'_> {
+    const COLUMNS: &'static [&'static str] = &[
+        "budget",
+        "period",
+        "limit",
+        "spent",
+        "remaining",
+        "currency",
+        "used",
+        "over_budget",
+    ];
+}
+
+/// Whether the payment counts against the budget, by the category of its expense if needed.
+fn is_in_budget(budget: &Budget, payment: &Payment, categories: &HashMap<String, String>) -> bool {
+    match &budget.target {
+        BudgetTarget::Category(category) => categories
+            .get(&payment.expense_name)
+            .is_some_and(|c| c.eq_ignore_ascii_case(category)),
+        BudgetTarget::Expense(expense) => payment.expense_name == *expense,
+    }
+}
+
+/// How much was spent against each budget in its period containing `today`. Payments in other
+/// currencies are converted to the one of the budget with `convert`, as of the day they were made.
+pub(crate) fn generate_budget_rows<'a, E>(
+    budgets: &'a [Budget],
+    payments: &[Payment],
+    categories: &HashMap<String, String>,
+    today: NaiveDate,
+    mut convert: impl FnMut(Money, Currency, NaiveDate) -> Result<Money, E>,
+) -> Result<Vec<BudgetRowDisplay<'a>>, E> {
+    budgets
+        .iter()
+        .map(|budget| {
+            let (start, period) = match budget.period {
+                BudgetPeriod::Month => (
+                    today.with_day(1).expect("every month has a 1st"),
+                    today.format("%Y-%m").to_string(),
+                ),
+                BudgetPeriod::Year => (
+                    today.with_ordinal(1).expect("every year has a 1st day"),
+                    today.year().to_string(),
+                ),
+            };
+            let end = match budget.period {
+                BudgetPeriod::Month => start + Months::new(1),
+                BudgetPeriod::Year => start + Months::new(12),
+            };
+
+            let mut spent = Decimal::ZERO;
+            for payment in payments {
+                let paid_at = payment.paid_at.with_timezone(&Local).date_naive();
+                let Some(amount) = payment.amount else {
+                    continue;
+                };
+                if paid_at < start || paid_at >= end || !is_in_budget(budget, payment, categories) {
+                    continue;
+                }
+                spent += convert(amount, budget.limit.currency, paid_at)?.amount.0;
+            }
+
+            let limit = budget.limit.amount.0;
+            let money = |amount: Decimal| {
+                Some(Money {
+                    amount: Amount(amount.round_dp(2)),
+                    currency: budget.limit.currency,
+                })
+            };
+
+            Ok(BudgetRowDisplay {
+                budget: budget.target.to_string(),
+                period,
+                limit: money(limit),
+                spent: money(spent),
+                remaining: money(limit - spent),
+                currency: budget.limit.currency,
+                used: (!limit.is_zero())
+                    .then(|| (spent / limit * Decimal::ONE_HUNDRED).round_dp(1)),
+                over_budget: spent > limit,
+                target: &budget.target,
+            })
+        })
+        .collect()
+}
+
+#[derive(Tabled, Serialize)]
+pub(crate) struct BalanceRowDisplay {
+    pub(crate) item: &'static str,
+    /// Everything due in the month, estimated for variable entries.
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) expected: Option<Money>,
+    /// What was actually paid, or received, for the due dates of the month.
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) paid_so_far: Option<Money>,
+    /// Amounts paid so far, plus the expected ones still to be paid.
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) projected: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Currency,
+}
+
+impl Columns for BalanceRowDisplay {
+    const COLUMNS: &'static [&'static str] =
+        &["item", "expected", "paid_so_far", "projected", "currency"];
+}
+
+#[derive(Default)]
+struct BalanceTotals {
+    expected: Decimal,
+    paid: Decimal,
+    projected: Decimal,
+}
+
+#[derive(Tabled, Serialize)]
+pub(crate) struct AccountRowDisplay<'a> {
+    #[tabled(display = "display_optional")]
+    pub(crate) account: Option<&'a str>,
+    pub(crate) due_dates: usize,
+    /// Left empty for due dates without an amount.
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) total: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Option<Currency>,
+    #[tabled(display = "display_date")]
+    pub(crate) next_due_date: Option<NaiveDate>,
+}
+
+impl Columns for AccountRowDisplay<'_> {
+    const COLUMNS: &'static [&'static str] =
+        &["account", "due_dates", "total", "currency", "next_due_date"];
+}
+
+/// The forecast due dates summed up per account the expenses are paid from, and per currency.
+/// Every account is listed, even with nothing due, followed by the expenses with no account.
+/// Amounts are converted with `convert`, as of the day they are due.
+pub(crate) fn generate_account_rows<'a, E>(
+    accounts: &'a [String],
+    expenses: &'a [Expense],
+    forecast: &[ForecastRowDisplay],
+    mut convert: impl FnMut(Money, NaiveDate) -> Result<Money, E>,
+) -> Result<Vec<AccountRowDisplay<'a>>, E> {
+    let expense_accounts: HashMap<&str, Option<&str>> = expenses
+        .iter()
+        .map(|expense| (expense.name.as_str(), expense.account.as_deref()))
+        .collect();
+
+    let mut totals = BTreeMap::new();
+    for row in forecast {
+        let account = expense_accounts.get(row.expense_name).copied().flatten();
+        let amount = row
+            .amount
+            .map(|amount| convert(amount, row.due_date))
+            .transpose()?;
+
+        let (due_dates, total, next_due_date) = totals
+            .entry((account, amount.map(|m| m.currency)))
+            .or_insert((0, Decimal::ZERO, row.due_date));
+        *due_dates += 1;
+        *total += amount.map_or(Decimal::ZERO, |m| m.amount.0);
+        *next_due_date = (*next_due_date).min(row.due_date);
+    }
+
+    let mut rows: Vec<_> = totals
+        .into_iter()
+        .map(
+            |((account, currency), (due_dates, total, next_due_date))| AccountRowDisplay {
+                account,
+                due_dates,
+                total: currency.map(|currency| Money {
+                    amount: Amount(total),
+                    currency,
+                }),
+                currency,
+                next_due_date: Some(next_due_date),
+            },
+        )
+        .collect();
+    for account in accounts {
+        if !rows.iter().any(|row| row.account == Some(account.as_str())) {
+            rows.push(AccountRowDisplay {
+                account: Some(account),
+                due_dates: 0,
+                total: None,
+                currency: None,
+                next_due_date: None,
+            });
+        }
+    }
+    rows.sort_by(|a, b| {
+        (a.account.is_none(), a.account, a.currency).cmp(&(
+            b.account.is_none(),
+            b.account,
+            b.currency,
+        ))
+    });
+
+    Ok(rows)
+}
+
+/// Income, expenses and their difference over the due dates that can be paid between `from` and
+/// `until`, per currency. Amounts are converted with `convert`, as of the day they are due.
+pub(crate) fn generate_balance_rows<E>(
+    entries: &[Expense],
+    payments: &[Payment],
+    calendar: &Calendar,
+    from: NaiveDate,
+    until: NaiveDate,
+    mut convert: impl FnMut(Money, NaiveDate) -> Result<Money, E>,
+) -> Result<Vec<BalanceRowDisplay>, E> {
+    // Income first, then expenses, per currency.
+    let mut totals: BTreeMap<Currency, [BalanceTotals; 2]> = BTreeMap::new();
+    for entry in entries {
+        for due_date in get_due_dates_between_aux(entry, calendar, &Local, from, until) {
+            let payable_date = get_payable_date(entry, calendar, &due_date);
+            let paid = payments
+                .iter()
+                .find(|p| p.expense_name == entry.name && p.due_date_of_expense == due_date)
+                .and_then(|p| p.amount)
+                .map(|amount| convert(amount, payable_date))
+                .transpose()?;
+            let expected = estimate_amount(entry, payments, &due_date)
+                .0
+                .map(|amount| convert(amount, payable_date))
+                .transpose()?;
+
+            if let Some(amount) = expected {
+                let side =
+                    &mut totals.entry(amount.currency).or_default()[usize::from(!entry.income)];
+                side.expected += amount.amount.0;
+                if paid.is_none() {
+                    side.projected += amount.amount.0;
+                }
+            }
+            if let Some(amount) = paid {
+                let side =
+                    &mut totals.entry(amount.currency).or_default()[usize::from(!entry.income)];
+                side.paid += amount.amount.0;
+                side.projected += amount.amount.0;
+            }
+        }
+    }
+
+    Ok(totals
+        .into_iter()
+        .flat_map(|(currency, [income, expenses])| {
+            let money = move |amount: Decimal| {
+                Some(Money {
+                    amount: Amount(amount.round_dp(2)),
+                    currency,
+                })
+            };
+            let row = move |item, expected, paid, projected| BalanceRowDisplay {
+                item,
+                expected: money(expected),
+                paid_so_far: money(paid),
+                projected: money(projected),
+                currency,
+            };
+
+            [
+                row("income", income.expected, income.paid, income.projected),
+                row(
+                    "expenses",
+                    expenses.expected,
+                    expenses.paid,
+                    expenses.projected,
+                ),
+                row(
+                    "net",
+                    income.expected - expenses.expected,
+                    income.paid - expenses.paid,
+                    income.projected - expenses.projected,
+                ),
+            ]
+        })
+        .collect())
+}
+
+/// What each member owes of `amount`, paid for an expense split by `split`. Shares add up to
+/// `amount`, the last member taking any rounding difference.
+pub(crate) fn get_shares(split: &Split, amount: Decimal) -> Vec<(&str, Decimal)> {
+    let count = Decimal::from(split.shares.len());
+    let share_of = |share: &Option<Amount>| share.map_or(Decimal::ZERO, |share| share.0);
+
+    let mut shares: Vec<_> = match split.rule {
+        SplitRule::Equal => split
+            .shares
+            .iter()
+            .map(|(member, _)| (member.as_str(), amount / count))
+            .collect(),
+        SplitRule::Percentage => split
+            .shares
+            .iter()
+            .map(|(member, share)| {
+                (
+                    member.as_str(),
+                    amount * share_of(share) / Decimal::ONE_HUNDRED,
+                )
+            })
+            .collect(),
+        SplitRule::Fixed => {
+            let fixed: Decimal = split.shares.iter().map(|(_, share)| share_of(share)).sum();
+            let difference = (amount - fixed) / count;
+            split
+                .shares
+                .iter()
+                .map(|(member, share)| (member.as_str(), share_of(share) + difference))
+                .collect()
+        }
+    };
+
+    for (_, share) in shares.iter_mut() {
+        *share = share.round_dp(2);
+    }
+    if let Some(((_, last), others)) = shares.split_last_mut() {
+        *last = amount - others.iter().map(|(_, share)| share).sum::<Decimal>();
+    }
+
+    shares
+}
+
+#[derive(Tabled, Serialize)]
+pub(crate) struct SettlementRowDisplay {
+    pub(crate) from: String,
+    pub(crate) to: String,
+    #[tabled(display = "display_amount")]
+    #[serde(serialize_with = "serialize_amount")]
+    pub(crate) amount: Option<Money>,
+    #[tabled(skip)]
+    pub(crate) currency: Currency,
+}
+
+impl Columns for SettlementRowDisplay {
+    const COLUMNS: &'static [&'static str] = &["from", "to", "amount", "currency"];
+}
+
+/// Transfers evening out the balances of the members, which are positive for those who paid more
+/// than their share. Each currency is settled on its own, the largest debts first.
+pub(crate) fn generate_settlement_rows(
+    balances: &BTreeMap<(Currency, String), Decimal>,
+) -> Vec<SettlementRowDisplay> {
+    let currencies: std::collections::BTreeSet<_> =
+        balances.keys().map(|(currency, _)| *currency).collect();
+    let mut rows = Vec::new();
+
+    for currency in currencies {
+        let members = || {
+            balances
+                .iter()
+                .filter(move |((c, _), _)| *c == currency)
+                .map(|((_, member), balance)| (member.clone(), *balance))
+        };
+        let mut creditors: Vec<_> = members().filter(|(_, b)| b.is_sign_positive()).collect();
+        let mut debtors: Vec<_> = members()
+            .filter(|(_, b)| b.is_sign_negative())
+            .map(|(member, balance)| (member, -balance))
+            .collect();
+        creditors.sort_by_key(|(_, balance)| std::cmp::Reverse(*balance));
+        debtors.sort_by_key(|(_, balance)| std::cmp::Reverse(*balance));
+
+        let (mut i, mut j) = (0, 0);
+        while i < debtors.len() && j < creditors.len() {
+            let amount = debtors[i].1.min(creditors[j].1);
+            if !amount.is_zero() {
+                rows.push(SettlementRowDisplay {
+                    from: debtors[i].0.clone(),
+                    to: creditors[j].0.clone(),
+                    amount: Some(Money {
+                        amount: Amount(amount),
+                        currency,
+                    }),
+                    currency,
+                });
+            }
+            debtors[i].1 -= amount;
+            creditors[j].1 -= amount;
+            if debtors[i].1.is_zero() {
+                i += 1;
+            }
+            if creditors[j].1.is_zero() {
+                j += 1;
+            }
+        }
+    }
+
+    rows
+}
+
+pub(crate) fn generate_history_rows(payments: &[Payment]) -> Vec<HistoryRowDisplay<'_>> {
+    payments
+        .iter()
+        .map(|payment| {
+            let paid_at = payment.paid_at.with_timezone(&Local).date_naive();
+            let due_date = payment
+                .due_date_of_expense
+                .with_timezone(&Local)
+                .date_naive();
+            let days_early = due_date.signed_duration_since(paid_at).num_days();
+
+            HistoryRowDisplay {
+                id: payment.id,
+                expense_name: &payment.expense_name,
+                paid_at,
+                due_date,
+                amount: payment.amount,
+                currency: payment.amount.map(|m| m.currency),
+                timing: describe_timing(days_early),
+                method: payment.method.as_deref(),
+                confirmation: payment.confirmation.as_deref(),
+                note: payment.note.as_deref(),
+                paid_by: payment.paid_by.as_deref(),
+                account: payment.account.as_deref(),
+                days_early,
+                payment,
             }
         })
         .collect()
@@ -145,6 +1503,54 @@ mod tests {
 
     use super::*;
 
+    fn date(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().to_utc()
+    }
+
+    /// An open-ended expense without an amount, created on its reference date.
+    fn expense(name: &str, recurrence: &str, reference: &str) -> Expense {
+        Expense {
+            id: 1,
+            created_at: date(reference),
+            name: name.to_string(),
+            recurrence: recurrence.parse().unwrap(),
+            due_date_reference: date(reference),
+            month_end: MonthEndPolicy::Clamp,
+            business_day: BusinessDayRule::None,
+            installments: None,
+            until: None,
+            amount: None,
+            estimate: None,
+            category: None,
+            tags: Vec::new(),
+            payee: None,
+            reference: None,
+            url: None,
+            notes: None,
+            income: false,
+            account: None,
+            pauses: Vec::new(),
+            archived_at: None,
+        }
+    }
+
+    /// A payment without an amount, made on the due date it covers.
+    fn payment(expense_name: &str, due_date: &str) -> Payment {
+        Payment {
+            id: 1,
+            created_at: date(due_date),
+            paid_at: date(due_date),
+            expense_name: expense_name.to_string(),
+            due_date_of_expense: date(due_date),
+            amount: None,
+            method: None,
+            confirmation: None,
+            note: None,
+            paid_by: None,
+            account: None,
+        }
+    }
+
     #[test]
     fn test_next_date() {
         let reference = DateTime::parse_from_rfc3339("2012-05-12T00:00:01+00:00")
@@ -154,14 +1560,24 @@ mod tests {
             .unwrap()
             .to_utc();
 
-        let next_due_date = get_next_due_date_aux(&reference, &now, Periodicity::Weekly);
+        let next_due_date = get_next_due_date_aux(
+            &reference,
+            &now,
+            &"weekly".parse().unwrap(),
+            MonthEndPolicy::Clamp,
+        );
         assert_eq!(
             next_due_date,
             DateTime::parse_from_rfc3339("2029-03-24T00:00:01+00:00")
                 .unwrap()
                 .to_utc()
         );
-        let next_due_date = get_next_due_date_aux(&reference, &now, Periodicity::Monthly);
+        let next_due_date = get_next_due_date_aux(
+            &reference,
+            &now,
+            &"monthly".parse().unwrap(),
+            MonthEndPolicy::Clamp,
+        );
         assert_eq!(
             next_due_date,
             DateTime::parse_from_rfc3339("2029-04-12T00:00:01+00:00")
@@ -169,4 +1585,612 @@ mod tests {
                 .to_utc()
         );
     }
+
+    #[test]
+    fn test_overdue_dates() {
+        let expense = Expense {
+            created_at: date("2025-01-20T12:00:00+00:00"),
+            ..expense("Electricity", "monthly", "2024-06-10T00:00:01+00:00")
+        };
+        let payment = Payment {
+            paid_at: date("2025-03-09T12:00:00+00:00"),
+            ..payment("Electricity", "2025-03-10T00:00:01+00:00")
+        };
+
+        let overdue = get_overdue_dates_aux(
+            &expense,
+            &[payment],
+            &Calendar::default(),
+            &date("2025-05-01T00:00:00+00:00"),
+        );
+        assert_eq!(
+            overdue,
+            vec![
+                date("2025-02-10T00:00:01+00:00"),
+                date("2025-04-10T00:00:01+00:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_business_day_adjustment() {
+        // 2025-05-10 is a Saturday.
+        let expense = Expense {
+            created_at: date("2025-01-01T12:00:00+00:00"),
+            business_day: BusinessDayRule::Following,
+            ..expense("Water", "monthly", "2025-01-10T00:00:01+00:00")
+        };
+        let calendar = Calendar::default();
+
+        // The due date of Saturday can still be paid on Monday.
+        let sunday = date("2025-05-11T12:00:00+00:00");
+        assert_eq!(
+            get_next_payable_due_date_aux(&expense, &calendar, &sunday),
+            Some(date("2025-05-10T00:00:01+00:00"))
+        );
+        assert_eq!(
+            get_overdue_dates_aux(&expense, &[], &calendar, &sunday).len(),
+            4
+        );
+
+        let tuesday = date("2025-05-13T12:00:00+00:00");
+        assert_eq!(
+            get_next_payable_due_date_aux(&expense, &calendar, &tuesday),
+            Some(date("2025-06-10T00:00:01+00:00"))
+        );
+        assert_eq!(
+            get_overdue_dates_aux(&expense, &[], &calendar, &tuesday).len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_installments() {
+        let expense = Expense {
+            created_at: date("2026-03-01T12:00:00+00:00"),
+            installments: Some(3),
+            ..expense("Sofa", "monthly", "2026-01-05T00:00:01+00:00")
+        };
+        let payment = Payment {
+            paid_at: date("2026-01-05T12:00:00+00:00"),
+            ..payment("Sofa", "2026-01-05T00:00:01+00:00")
+        };
+        let payments = [payment];
+        let calendar = Calendar::default();
+
+        // Installments before the creation of the expense are still due.
+        let now = date("2026-02-10T00:00:00+00:00");
+        assert_eq!(
+            get_overdue_dates_aux(&expense, &payments, &calendar, &now),
+            vec![date("2026-02-05T00:00:01+00:00")]
+        );
+        assert_eq!(
+            get_next_payable_due_date_aux(&expense, &calendar, &now),
+            Some(date("2026-03-05T00:00:01+00:00"))
+        );
+        assert_eq!(get_progress(&expense, &payments), Some((1, 3)));
+
+        let now = date("2026-06-01T00:00:00+00:00");
+        assert_eq!(
+            get_overdue_dates_aux(&expense, &payments, &calendar, &now).len(),
+            2
+        );
+        assert_eq!(
+            get_next_payable_due_date_aux(&expense, &calendar, &now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_paying_due_dates() {
+        let local = |s: &str| from_local(&Local, s.parse().unwrap()).to_utc();
+        let day = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let expense = Expense {
+            due_date_reference: local("2026-01-05T00:00:01"),
+            installments: Some(3),
+            ..expense("Sofa", "monthly", "2026-01-05T00:00:01+00:00")
+        };
+        let second = local("2026-02-05T00:00:01");
+        let third = local("2026-03-05T00:00:01");
+        let calendar = Calendar::default();
+
+        // Only due dates can be paid with --for.
+        assert_eq!(
+            get_due_date_on(&expense, &calendar, day("2026-02-05")),
+            Some(second)
+        );
+        assert_eq!(
+            get_due_date_on(&expense, &calendar, day("2026-02-06")),
+            None
+        );
+
+        // --periods cannot go past the last installment.
+        assert_eq!(
+            get_consecutive_due_dates(&expense, &second, 2),
+            vec![second, third]
+        );
+        assert_eq!(get_consecutive_due_dates(&expense, &second, 3).len(), 2);
+
+        // Due dates already paid are refused.
+        let payments = [Payment {
+            due_date_of_expense: second,
+            ..payment("Sofa", "2026-02-05T00:00:01+00:00")
+        }];
+        let due_dates = get_consecutive_due_dates(&expense, &second, 2);
+        assert!(is_paid(&expense, &payments, &due_dates[0]));
+        assert!(!is_paid(&expense, &payments, &due_dates[1]));
+    }
+
+    #[test]
+    fn test_pauses() {
+        let day = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let mut expense = Expense {
+            created_at: date("2025-01-01T12:00:00+00:00"),
+            pauses: vec![Pause {
+                id: 1,
+                start_date: day("2025-02-01"),
+                end_date: Some(day("2025-03-31")),
+            }],
+            ..expense("Gym", "monthly", "2025-01-10T00:00:01+00:00")
+        };
+        let calendar = Calendar::default();
+        let now = date("2025-03-20T00:00:00+00:00");
+
+        assert_eq!(
+            get_overdue_dates_aux(&expense, &[], &calendar, &now),
+            vec![date("2025-01-10T00:00:01+00:00")]
+        );
+        assert_eq!(
+            get_next_payable_due_date_aux(&expense, &calendar, &now),
+            Some(date("2025-04-10T00:00:01+00:00"))
+        );
+
+        expense.pauses[0].end_date = None;
+        assert_eq!(
+            get_next_payable_due_date_aux(&expense, &calendar, &now),
+            None
+        );
+    }
+
+    #[test]
+    fn test_estimates() {
+        let money = |amount: &str, currency: &str| Money {
+            amount: amount.parse().unwrap(),
+            currency: currency.parse().unwrap(),
+        };
+        let mut expense = Expense {
+            created_at: date("2025-01-01T12:00:00+00:00"),
+            amount: Some(money("150", "BRL")),
+            ..expense("Electricity", "monthly", "2025-01-10T12:00:00+00:00")
+        };
+        let payment = |due_date, amount| Payment {
+            amount: Some(amount),
+            ..payment("Electricity", due_date)
+        };
+        let payments = [
+            payment("2025-03-10T12:00:00+00:00", money("50", "USD")),
+            payment("2025-04-10T12:00:00+00:00", money("300", "BRL")),
+            payment("2026-01-10T12:00:00+00:00", money("100", "BRL")),
+            payment("2026-02-10T12:00:00+00:00", money("120", "BRL")),
+            payment("2026-03-10T12:00:00+00:00", money("200", "BRL")),
+        ];
+        let due_date = date("2026-04-10T12:00:00+00:00");
+        let estimate = |expense: &Expense, payments: &[Payment]| {
+            let (amount, estimated) = estimate_amount(expense, payments, &due_date);
+            (amount.unwrap().to_string(), estimated)
+        };
+        let estimated = |amount: &str| (amount.to_string(), true);
+        let set = |amount: &str| (amount.to_string(), false);
+
+        // Fixed expenses keep their amount.
+        assert_eq!(estimate(&expense, &payments), set("150.00 BRL"));
+
+        expense.estimate = Some(Estimate::Last);
+        assert_eq!(estimate(&expense, &payments), estimated("200.00 BRL"));
+        expense.estimate = Some(Estimate::Average);
+        assert_eq!(estimate(&expense, &payments), estimated("140.00 BRL"));
+        expense.estimate = Some(Estimate::SameMonth);
+        assert_eq!(estimate(&expense, &payments), estimated("300.00 BRL"));
+        // Without a payment a year before, the average is used.
+        assert_eq!(estimate(&expense, &payments[2..]), estimated("140.00 BRL"));
+        // Before the first payment, the amount of the expense is used, as given.
+        assert_eq!(estimate(&expense, &[]), set("150.00 BRL"));
+
+        // Overdue bills are shown with the estimate for their own due date.
+        expense.estimate = Some(Estimate::Average);
+        let expenses = [expense];
+        let rows = generate_overdue_rows(&expenses, &payments, &Calendar::default());
+        let row = rows
+            .iter()
+            .find(|row| row.due_date == due_date.date_naive())
+            .unwrap();
+        assert_eq!(row.amount_text, "140.00 BRL (est.)");
+        assert!(row.estimated);
+    }
+
+    #[test]
+    fn test_upcoming_due_dates() {
+        let expense = Expense {
+            created_at: date("2026-01-01T12:00:00+00:00"),
+            until: Some("2026-09-30".parse().unwrap()),
+            pauses: vec![Pause {
+                id: 1,
+                start_date: "2026-06-01".parse().unwrap(),
+                end_date: Some("2026-06-30".parse().unwrap()),
+            }],
+            ..expense("Gym", "monthly", "2026-01-15T00:00:01+00:00")
+        };
+        // Paid in advance.
+        let payment = Payment {
+            paid_at: date("2026-04-01T12:00:00+00:00"),
+            ..payment("Gym", "2026-05-15T00:00:01+00:00")
+        };
+        let now = date("2026-04-10T00:00:00+00:00");
+        let upcoming = |until: &str| {
+            get_upcoming_due_dates_aux(
+                &expense,
+                std::slice::from_ref(&payment),
+                &Calendar::default(),
+                &now,
+                until.parse().unwrap(),
+            )
+        };
+
+        assert_eq!(
+            upcoming("2026-08-14"),
+            vec![
+                date("2026-04-15T00:00:01+00:00"),
+                date("2026-07-15T00:00:01+00:00"),
+            ]
+        );
+        // The expense ends before the forecast does.
+        assert_eq!(upcoming("2026-12-31").len(), 4);
+        assert!(upcoming("2026-04-14").is_empty());
+    }
+
+    #[test]
+    fn test_report() {
+        let paid = |date: &str, amount: &str, currency: &str| {
+            (
+                date.parse().unwrap(),
+                Money {
+                    amount: amount.parse().unwrap(),
+                    currency: currency.parse().unwrap(),
+                },
+            )
+        };
+        let payments = [
+            paid("2025-01-05", "100", "BRL"),
+            paid("2025-01-20", "50", "BRL"),
+            paid("2025-02-05", "120", "BRL"),
+            paid("2025-04-05", "90", "BRL"),
+            paid("2025-02-10", "10", "USD"),
+        ];
+
+        crate::output::assert_columns(&generate_report_rows(&payments, ReportPeriod::Month));
+        let rows: Vec<_> = generate_report_rows(&payments, ReportPeriod::Month)
+            .into_iter()
+            .map(|row| {
+                (
+                    row.period,
+                    row.total.unwrap().to_string(),
+                    row.payments,
+                    row.average.unwrap().to_string(),
+                    row.change.map(|c| c.to_string()),
+                    row.change_percent.map(|c| c.to_string()),
+                )
+            })
+            .collect();
+        let row = |period: &str,
+                   total: &str,
+                   payments,
+                   average: &str,
+                   change: Option<&str>,
+                   percent: Option<&str>| {
+            (
+                period.to_string(),
+                total.to_string(),
+                payments,
+                average.to_string(),
+                change.map(str::to_string),
+                percent.map(str::to_string),
+            )
+        };
+        assert_eq!(
+            rows,
+            [
+                row("2025-01", "150.00 BRL", 2, "75.00 BRL", None, None),
+                row(
+                    "2025-02",
+                    "120.00 BRL",
+                    1,
+                    "120.00 BRL",
+                    Some("-30"),
+                    Some("-20.0")
+                ),
+                row("2025-02", "10.00 USD", 1, "10.00 USD", Some("10"), None),
+                row("2025-04", "90.00 BRL", 1, "90.00 BRL", Some("90"), None),
+            ]
+        );
+
+        let quarters: Vec<_> = generate_report_rows(&payments, ReportPeriod::Quarter)
+            .into_iter()
+            .map(|row| (row.period, row.total.unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            quarters[0],
+            ("2025-Q1".to_string(), "270.00 BRL".to_string())
+        );
+        assert_eq!(
+            quarters[2],
+            ("2025-Q2".to_string(), "90.00 BRL".to_string())
+        );
+    }
+
+    #[test]
+    fn test_budgets() {
+        let money = |amount: &str, currency: &str| Money {
+            amount: amount.parse().unwrap(),
+            currency: currency.parse().unwrap(),
+        };
+        let payment = |name: &str, paid_at, amount| Payment {
+            amount: Some(amount),
+            ..payment(name, paid_at)
+        };
+        let payments = [
+            payment("Water", "2026-03-05T12:00:00+00:00", money("80", "BRL")),
+            payment("Power", "2026-03-10T12:00:00+00:00", money("30", "USD")),
+            payment("Power", "2026-02-10T12:00:00+00:00", money("200", "BRL")),
+            payment("Netflix", "2026-03-15T12:00:00+00:00", money("45", "BRL")),
+        ];
+        let categories: HashMap<_, _> = [
+            ("Water".to_string(), "Utilities".to_string()),
+            ("Power".to_string(), "Utilities".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let budgets = [
+            Budget {
+                id: 1,
+                target: BudgetTarget::Category("utilities".to_string()),
+                period: BudgetPeriod::Month,
+                limit: money("200", "BRL"),
+            },
+            Budget {
+                id: 2,
+                target: BudgetTarget::Expense("Power".to_string()),
+                period: BudgetPeriod::Year,
+                limit: money("400", "BRL"),
+            },
+        ];
+        // Dollars are worth 5 reais.
+        let convert = |money: Money, currency: Currency, _| -> Result<Money, ()> {
+            Ok(Money {
+                amount: Amount(
+                    money.amount.0 * Decimal::from(if money.currency == currency { 1 } else { 5 }),
+                ),
+                currency,
+            })
+        };
+
+        let rows = generate_budget_rows(
+            &budgets,
+            &payments,
+            &categories,
+            "2026-03-20".parse().unwrap(),
+            convert,
+        )
+        .unwrap();
+        crate::output::assert_columns(&rows);
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.period.as_str(),
+                    row.spent.unwrap().to_string(),
+                    row.remaining.unwrap().to_string(),
+                    row.over_budget,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "2026-03",
+                    "230.00 BRL".to_string(),
+                    "-30.00 BRL".to_string(),
+                    true
+                ),
+                (
+                    "2026",
+                    "350.00 BRL".to_string(),
+                    "50.00 BRL".to_string(),
+                    false
+                ),
+            ]
+        );
+        assert_eq!(rows[0].used.unwrap().to_string(), "115.0");
+    }
+
+    #[test]
+    fn test_balance() {
+        let money = |amount: &str| Money {
+            amount: amount.parse().unwrap(),
+            currency: "BRL".parse().unwrap(),
+        };
+        let entry = |name: &str, recurrence: &str, amount, income| Expense {
+            created_at: date("2026-01-01T12:00:00+00:00"),
+            amount: Some(money(amount)),
+            income,
+            ..expense(name, recurrence, "2026-01-05T12:00:00+00:00")
+        };
+        let entries = [
+            entry("Salary", "monthly", "5000", true),
+            entry("Cleaning", "every 2 weeks", "100", false),
+            entry("Internet", "monthly", "100", false),
+        ];
+        // Received in full, and paid more than expected.
+        let payment = |name: &str, due_date, amount| Payment {
+            amount: Some(money(amount)),
+            ..payment(name, due_date)
+        };
+        let payments = [
+            payment("Salary", "2026-03-05T12:00:00+00:00", "5000"),
+            payment("Internet", "2026-03-05T12:00:00+00:00", "120"),
+        ];
+
+        let rows = generate_balance_rows(
+            &entries,
+            &payments,
+            &Calendar::default(),
+            "2026-03-01".parse().unwrap(),
+            "2026-03-31".parse().unwrap(),
+            |money, _| Ok::<_, ()>(money),
+        )
+        .unwrap();
+        crate::output::assert_columns(&rows);
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                [row.expected, row.paid_so_far, row.projected].map(|m| m.unwrap().to_string())
+            })
+            .collect();
+        // Cleaning is due on March 2nd, 16th and 30th.
+        assert_eq!(
+            rows,
+            [
+                ["5000.00 BRL", "5000.00 BRL", "5000.00 BRL"],
+                ["400.00 BRL", "120.00 BRL", "420.00 BRL"],
+                ["4600.00 BRL", "4880.00 BRL", "4580.00 BRL"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_settlement() {
+        let member = |name: &str, share: Option<&str>| {
+            (name.to_string(), share.map(|share| share.parse().unwrap()))
+        };
+        let split = |rule, shares| Split { rule, shares };
+        let shares = |split: &Split, amount: &str| -> Vec<String> {
+            get_shares(split, amount.parse().unwrap())
+                .into_iter()
+                .map(|(member, share)| format!("{member} {share}"))
+                .collect()
+        };
+
+        let equal = split(
+            SplitRule::Equal,
+            vec![
+                member("Ana", None),
+                member("Bia", None),
+                member("Caio", None),
+            ],
+        );
+        assert_eq!(
+            shares(&equal, "100"),
+            ["Ana 33.33", "Bia 33.33", "Caio 33.34"]
+        );
+        let percentage = split(
+            SplitRule::Percentage,
+            vec![member("Ana", Some("60")), member("Bia", Some("40"))],
+        );
+        assert_eq!(shares(&percentage, "150"), ["Ana 90", "Bia 60"]);
+        let fixed = split(
+            SplitRule::Fixed,
+            vec![member("Ana", Some("800")), member("Bia", Some("700"))],
+        );
+        assert_eq!(shares(&fixed, "1600"), ["Ana 850", "Bia 750"]);
+
+        let brl: Currency = "BRL".parse().unwrap();
+        let balances: BTreeMap<_, _> = [
+            ((brl, "Ana".to_string()), Decimal::from(90)),
+            ((brl, "Bia".to_string()), Decimal::from(-60)),
+            ((brl, "Caio".to_string()), Decimal::from(-30)),
+        ]
+        .into_iter()
+        .collect();
+        crate::output::assert_columns(&generate_settlement_rows(&balances));
+        let transfers: Vec<_> = generate_settlement_rows(&balances)
+            .into_iter()
+            .map(|row| format!("{} -> {}: {}", row.from, row.to, row.amount.unwrap()))
+            .collect();
+        assert_eq!(
+            transfers,
+            ["Bia -> Ana: 60.00 BRL", "Caio -> Ana: 30.00 BRL"]
+        );
+    }
+
+    #[test]
+    fn test_accounts() {
+        let money = |amount: &str| Money {
+            amount: amount.parse().unwrap(),
+            currency: "BRL".parse().unwrap(),
+        };
+        let expense = |name: &str, account: Option<&str>| Expense {
+            created_at: date("2026-01-01T12:00:00+00:00"),
+            account: account.map(str::to_string),
+            ..expense(name, "monthly", "2026-01-05T12:00:00+00:00")
+        };
+        let forecast = |due_date: &str, expense_name, amount: Option<&str>| {
+            let amount = amount.map(money);
+            ForecastRowDisplay {
+                due_date: due_date.parse().unwrap(),
+                expense_name,
+                amount_text: describe_amount(amount, false),
+                amount,
+                currency: amount.map(|m| m.currency),
+                estimated: false,
+            }
+        };
+
+        let accounts = [
+            "Checking".to_string(),
+            "Credit card".to_string(),
+            "PIX".to_string(),
+        ];
+        let expenses = [
+            expense("Rent", Some("Checking")),
+            expense("Electricity", Some("Checking")),
+            expense("Netflix", Some("Credit card")),
+            expense("Gym", None),
+        ];
+        let rows = generate_account_rows(
+            &accounts,
+            &expenses,
+            &[
+                forecast("2026-03-05", "Electricity", Some("180")),
+                forecast("2026-03-10", "Rent", Some("1500")),
+                forecast("2026-03-15", "Netflix", Some("44.90")),
+                forecast("2026-04-05", "Electricity", Some("180")),
+                forecast("2026-03-20", "Gym", None),
+            ],
+            |money, _| Ok::<_, ()>(money),
+        )
+        .unwrap();
+        crate::output::assert_columns(&rows);
+
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "{} {} {} {}",
+                    display_optional(&row.account),
+                    row.due_dates,
+                    display_amount(&row.total),
+                    display_date(&row.next_due_date)
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "Checking 3 1860.00 BRL 2026-03-05",
+                "Credit card 1 44.90 BRL 2026-03-15",
+                "PIX 0 - -",
+                "- 1 - 2026-03-20",
+            ]
+        );
+    }
 }